@@ -1,7 +1,10 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection, Result};
+use std::collections::HashSet;
 
-use crate::models::{DomainOverride, EmailConfig, Feed, ContentProcessor, GeneralConfig, ProcessorType, ReadItLaterArticle, Schedule};
+use crate::feed::Article;
+use crate::util::content_extractors::FetchedContent;
+use crate::models::{ArticleOrder, CategorySummary, DigestLayout, DomainCookies, DomainOverride, EmailConfig, Feed, FeedAuth, FeedFilter, FeedHealth, ContentProcessor, FetchMode, GeneralConfig, ProcessorType, ReadItLaterArticle, Schedule, UpdateFeedRequest};
 
 pub mod schema_init;
 
@@ -10,10 +13,14 @@ pub fn add_feed(
     url: &str,
     name: Option<&str>,
     concurrency_limit: usize,
+    fetch_since_hours: Option<i32>,
+    max_items: Option<usize>,
+    category: Option<&str>,
 ) -> Result<i64> {
     conn.execute(
-        "INSERT OR IGNORE INTO feeds (url, name, concurrency_limit, created_at) VALUES (?1, ?2, ?3, ?4)",
-        params![url, name, concurrency_limit, Utc::now().to_rfc3339()],
+        "INSERT OR IGNORE INTO feeds (url, name, concurrency_limit, fetch_since_hours, max_items, category, created_at, priority)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, (SELECT COALESCE(MAX(priority), -1) + 1 FROM feeds))",
+        params![url, name, concurrency_limit, fetch_since_hours, max_items, category, Utc::now().to_rfc3339()],
     )?;
     let x= conn.last_insert_rowid();
     Ok(x)
@@ -21,9 +28,16 @@ pub fn add_feed(
 
 pub fn get_feeds(conn: &Connection) -> Result<Vec<Feed>> {
     let mut stmt = conn.prepare(
-        "SELECT f.id, f.url, f.name, f.concurrency_limit, fp.processor, fp.custom_config
+        "SELECT f.id, f.url, f.name, f.concurrency_limit, fp.processor, fp.custom_config, f.etag, f.last_modified,
+                f.last_delivered_at, f.fetch_since_hours, f.max_items, f.enabled, f.last_attempt_at,
+                f.last_success_at, f.consecutive_failures, f.last_http_status, f.last_error, ff.rules,
+                fa.username, fa.password, fa.bearer_token, fa.headers, fa.feed_id, f.proxy_url, f.last_retry_count, f.category,
+                f.priority, f.poll_interval_minutes, fp.fallbacks
          FROM feeds f
-         LEFT JOIN feed_processor fp ON f.id = fp.feed_id"
+         LEFT JOIN feed_processor fp ON f.id = fp.feed_id
+         LEFT JOIN feed_filter ff ON f.id = ff.feed_id
+         LEFT JOIN feed_auth fa ON f.id = fa.feed_id
+         ORDER BY f.priority, f.id"
     )?;
     let feed_iter = stmt.query_map([], |row| {
         let feed_id: i64 = row.get(0)?;
@@ -42,7 +56,31 @@ pub fn get_feeds(conn: &Connection) -> Result<Vec<Feed>> {
                 id: Some(feed_id),
                 processor,
                 custom_config,
+                fallbacks: parse_fallbacks(row.get(28)?),
             },
+            etag: row.get(6)?,
+            last_modified: row.get(7)?,
+            last_delivered_at: row.get(8)?,
+            fetch_since_hours: row.get(9)?,
+            max_items: row.get(10)?,
+            enabled: row.get(11)?,
+            health: FeedHealth {
+                last_attempt_at: row.get(12)?,
+                last_success_at: row.get(13)?,
+                consecutive_failures: row.get(14)?,
+                last_http_status: row.get(15)?,
+                last_error: row.get(16)?,
+                last_retry_count: row.get(24)?,
+            },
+            filter: parse_feed_filter(row.get(17)?),
+            auth: match row.get::<_, Option<i64>>(22)? {
+                Some(_) => Some(feed_auth_from_row(row, 18)?),
+                None => None,
+            },
+            proxy_url: row.get(23)?,
+            category: row.get(25)?,
+            priority: row.get(26)?,
+            poll_interval_minutes: row.get(27)?,
         })
     })?;
 
@@ -53,11 +91,442 @@ pub fn get_feeds(conn: &Connection) -> Result<Vec<Feed>> {
     Ok(feeds)
 }
 
+pub fn get_feed_id_by_url(conn: &Connection, url: &str) -> Result<Option<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM feeds WHERE url = ?1")?;
+    let mut iter = stmt.query_map(params![url], |row| row.get(0))?;
+    iter.next().transpose()
+}
+
+pub fn update_feed_category(conn: &Connection, id: i64, category: Option<&str>) -> Result<()> {
+    conn.execute("UPDATE feeds SET category = ?1 WHERE id = ?2", params![category, id])?;
+    Ok(())
+}
+
+/// Stores the user's feed order. Returns false, changing nothing, unless `feed_ids` lists every
+/// feed exactly once.
+pub fn reorder_feeds(conn: &Connection, feed_ids: &[i64]) -> Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    let mut existing: Vec<i64> = tx
+        .prepare("SELECT id FROM feeds")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    let mut requested = feed_ids.to_vec();
+    existing.sort_unstable();
+    requested.sort_unstable();
+    if existing != requested {
+        return Ok(false);
+    }
+
+    {
+        let mut stmt = tx.prepare("UPDATE feeds SET priority = ?1 WHERE id = ?2")?;
+        for (position, id) in feed_ids.iter().enumerate() {
+            stmt.execute(params![position as i64, id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+pub fn get_categories(conn: &Connection) -> Result<Vec<CategorySummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT category, COUNT(*) FROM feeds WHERE category IS NOT NULL AND category != ''
+         GROUP BY category ORDER BY category",
+    )?;
+    let iter = stmt.query_map([], |row| {
+        Ok(CategorySummary {
+            name: row.get(0)?,
+            feed_count: row.get(1)?,
+        })
+    })?;
+    iter.collect()
+}
+
+/// Renames a category along with every sub-category below it. Returns the number of feeds moved.
+pub fn rename_category(conn: &Connection, from: &str, to: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE feeds SET category = ?2 || substr(category, length(?1) + 1)
+         WHERE category = ?1 OR substr(category, 1, length(?1) + 1) = ?1 || '/'",
+        params![from, to],
+    )
+}
+
+/// Applies the fields present in `feed`; returns the number of matching feeds (0 if `id` is unknown).
+pub fn update_feed(conn: &Connection, id: i64, feed: &UpdateFeedRequest) -> Result<usize> {
+    let mut sets: Vec<&str> = Vec::new();
+    let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(name) = &feed.name {
+        sets.push("name = ?");
+        values.push(Box::new(name.clone()));
+    }
+    if let Some(limit) = feed.concurrency_limit {
+        sets.push("concurrency_limit = ?");
+        values.push(Box::new(limit));
+    }
+    if let Some(hours) = feed.fetch_since_hours {
+        sets.push("fetch_since_hours = ?");
+        values.push(Box::new(hours));
+    }
+    if let Some(max_items) = feed.max_items {
+        sets.push("max_items = ?");
+        values.push(Box::new(max_items));
+    }
+    if let Some(enabled) = feed.enabled {
+        // Re-enabling a feed gives it a clean failure count so it is not disabled again on the next error.
+        sets.push("consecutive_failures = CASE WHEN enabled = 0 AND ? = 1 THEN 0 ELSE consecutive_failures END");
+        values.push(Box::new(enabled));
+        sets.push("enabled = ?");
+        values.push(Box::new(enabled));
+    }
+    if let Some(proxy_url) = &feed.proxy_url {
+        sets.push("proxy_url = ?");
+        values.push(Box::new(proxy_url.clone()));
+    }
+    if let Some(category) = &feed.category {
+        sets.push("category = ?");
+        values.push(Box::new(category.clone()));
+    }
+    if let Some(minutes) = feed.poll_interval_minutes {
+        sets.push("poll_interval_minutes = ?");
+        values.push(Box::new(minutes));
+    }
+    if sets.is_empty() {
+        return conn.query_row("SELECT COUNT(*) FROM feeds WHERE id = ?1", params![id], |row| row.get(0));
+    }
+
+    values.push(Box::new(id));
+    let sql = format!("UPDATE feeds SET {} WHERE id = ?", sets.join(", "));
+    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))
+}
+
+/// Records the outcome of a feed fetch. Returns true if the feed was disabled because of it.
+pub fn record_feed_fetch(
+    conn: &Connection,
+    id: i64,
+    http_status: Option<u16>,
+    error: Option<&str>,
+    retries: u32,
+    auto_disable_after_failures: i32,
+) -> Result<bool> {
+    let now = Utc::now().to_rfc3339();
+    if error.is_none() {
+        conn.execute(
+            "UPDATE feeds SET last_attempt_at = ?1, last_success_at = ?1, consecutive_failures = 0,
+                    last_http_status = ?2, last_error = NULL, last_retry_count = ?4
+             WHERE id = ?3",
+            params![now, http_status, id, retries],
+        )?;
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE feeds SET last_attempt_at = ?1, consecutive_failures = consecutive_failures + 1,
+                last_http_status = ?2, last_error = ?3, last_retry_count = ?5
+         WHERE id = ?4",
+        params![now, http_status, error, id, retries],
+    )?;
+    if auto_disable_after_failures <= 0 {
+        return Ok(false);
+    }
+    let disabled = conn.execute(
+        "UPDATE feeds SET enabled = 0 WHERE id = ?1 AND enabled = 1 AND consecutive_failures >= ?2",
+        params![id, auto_disable_after_failures],
+    )?;
+    Ok(disabled > 0)
+}
+
+/// Adds retries spent after the feed fetch itself, on its article pages and images.
+pub fn add_feed_retries(conn: &Connection, id: i64, retries: u32) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET last_retry_count = last_retry_count + ?1 WHERE id = ?2",
+        params![retries, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_feed_cache_headers(
+    conn: &Connection,
+    id: i64,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET etag = ?1, last_modified = ?2 WHERE id = ?3",
+        params![etag, last_modified, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_feed_last_delivered(conn: &Connection, id: i64, delivered_at: DateTime<Utc>) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET last_delivered_at = ?1 WHERE id = ?2",
+        params![delivered_at.to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+pub fn get_seen_item_keys(conn: &Connection, feed_id: i64) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT item_key FROM seen_items WHERE feed_id = ?1")?;
+    let iter = stmt.query_map(params![feed_id], |row| row.get(0))?;
+
+    let mut keys = HashSet::new();
+    for key in iter {
+        keys.insert(key?);
+    }
+    Ok(keys)
+}
+
+pub fn mark_items_seen(
+    conn: &Connection,
+    items: &[(i64, String)],
+    delivered_at: DateTime<Utc>,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO seen_items (feed_id, item_key, delivered_at) VALUES (?1, ?2, ?3)",
+        )?;
+        let delivered_at = delivered_at.to_rfc3339();
+        for (feed_id, item_key) in items {
+            stmt.execute(params![feed_id, item_key, delivered_at])?;
+        }
+    }
+    tx.commit()
+}
+
+pub fn prune_first_seen(conn: &Connection, older_than: DateTime<Utc>) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM item_first_seen WHERE first_seen_at < ?1",
+        params![older_than.to_rfc3339()],
+    )
+}
+
+/// Returns when an undated item was first fetched, recording `now` if it is new.
+pub fn get_or_insert_first_seen(
+    conn: &Connection,
+    feed_id: i64,
+    item_key: &str,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>> {
+    conn.execute(
+        "INSERT OR IGNORE INTO item_first_seen (feed_id, item_key, first_seen_at) VALUES (?1, ?2, ?3)",
+        params![feed_id, item_key, now.to_rfc3339()],
+    )?;
+    let first_seen: String = conn.query_row(
+        "SELECT first_seen_at FROM item_first_seen WHERE feed_id = ?1 AND item_key = ?2",
+        params![feed_id, item_key],
+        |row| row.get(0),
+    )?;
+    Ok(DateTime::parse_from_rfc3339(&first_seen)
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or(now))
+}
+
+/// Returns the longest per-feed fetch window, if any feed overrides the global one.
+pub fn get_max_feed_fetch_since_hours(conn: &Connection) -> Result<Option<i32>> {
+    conn.query_row("SELECT MAX(fetch_since_hours) FROM feeds", [], |row| row.get(0))
+}
+
+pub fn prune_seen_items(conn: &Connection, older_than: DateTime<Utc>) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM seen_items WHERE delivered_at < ?1",
+        params![older_than.to_rfc3339()],
+    )
+}
+
 pub fn delete_feed(conn: &Connection, id: i64) -> Result<()> {
     conn.execute("DELETE FROM feeds WHERE id = ?1", params![id])?;
+    conn.execute("DELETE FROM seen_items WHERE feed_id = ?1", params![id])?;
+    conn.execute("DELETE FROM item_first_seen WHERE feed_id = ?1", params![id])?;
+    conn.execute("DELETE FROM feed_filter WHERE feed_id = ?1", params![id])?;
+    conn.execute("DELETE FROM feed_auth WHERE feed_id = ?1", params![id])?;
+    conn.execute("DELETE FROM stored_articles WHERE feed_id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Saves polled articles for a later digest; items already stored are left untouched.
+pub fn store_articles(conn: &Connection, articles: &[Article], fetched_at: DateTime<Utc>) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut stored = 0;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO stored_articles (feed_id, item_key, title, link, content, pub_date, source, fetched_at,
+                                                    author, tags, excerpt, lead_image, canonical_url)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        let fetched_at = fetched_at.to_rfc3339();
+        for article in articles {
+            let (Some(feed_id), Some(item_key)) = (article.feed_id, &article.item_key) else { continue };
+            stored += stmt.execute(params![
+                feed_id,
+                item_key,
+                article.title,
+                article.link,
+                article.content,
+                article.pub_date.to_rfc3339(),
+                article.source,
+                fetched_at,
+                article.author,
+                serde_json::to_string(&article.tags).unwrap_or_default(),
+                article.excerpt,
+                article.lead_image,
+                article.canonical_url
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(stored)
+}
+
+pub fn get_stored_item_keys(conn: &Connection, feed_id: i64) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT item_key FROM stored_articles WHERE feed_id = ?1")?;
+    let iter = stmt.query_map(params![feed_id], |row| row.get(0))?;
+    iter.collect()
+}
+
+/// Stored articles not yet delivered, newest first.
+pub fn get_undelivered_articles(conn: &Connection) -> Result<Vec<Article>> {
+    let mut stmt = conn.prepare(
+        "SELECT a.feed_id, a.item_key, a.title, a.link, a.content, a.pub_date, a.source, f.category, f.priority,
+                a.author, a.tags, a.excerpt, a.lead_image, a.canonical_url
+         FROM stored_articles a
+         JOIN feeds f ON f.id = a.feed_id
+         WHERE a.delivered_at IS NULL
+         ORDER BY a.pub_date DESC",
+    )?;
+    let iter = stmt.query_map([], |row| {
+        let pub_date: String = row.get(5)?;
+        let tags: Option<String> = row.get(10)?;
+        Ok(Article {
+            feed_id: row.get(0)?,
+            item_key: row.get(1)?,
+            title: row.get(2)?,
+            link: row.get(3)?,
+            content: row.get(4)?,
+            pub_date: DateTime::parse_from_rfc3339(&pub_date)
+                .map(|d| d.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            source: row.get(6)?,
+            category: row.get(7)?,
+            priority: row.get(8)?,
+            duplicate_keys: Vec::new(),
+            author: row.get(9)?,
+            tags: tags.and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default(),
+            excerpt: row.get(11)?,
+            lead_image: row.get(12)?,
+            canonical_url: row.get(13)?,
+            retries: 0,
+        })
+    })?;
+    iter.collect()
+}
+
+pub fn mark_stored_articles_delivered(
+    conn: &Connection,
+    items: &[(i64, String)],
+    delivered_at: DateTime<Utc>,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE stored_articles SET delivered_at = ?3 WHERE feed_id = ?1 AND item_key = ?2",
+        )?;
+        let delivered_at = delivered_at.to_rfc3339();
+        for (feed_id, item_key) in items {
+            stmt.execute(params![feed_id, item_key, delivered_at])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Removes articles fetched before `older_than`. Callers pass the retention horizon, past which an
+/// undelivered article has been left out of every digest since it was stored.
+pub fn prune_stored_articles(conn: &Connection, older_than: DateTime<Utc>) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM stored_articles WHERE fetched_at < ?1",
+        params![older_than.to_rfc3339()],
+    )
+}
+
+pub fn get_cached_page(conn: &Connection, url: &str, fresh_after: DateTime<Utc>) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT html FROM page_cache WHERE url = ?1 AND fetched_at >= ?2")?;
+    let mut iter = stmt.query_map(params![url, fresh_after.to_rfc3339()], |row| row.get(0))?;
+    iter.next().transpose()
+}
+
+pub fn save_cached_page(conn: &Connection, url: &str, html: &str, fetched_at: DateTime<Utc>) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO page_cache (url, html, fetched_at) VALUES (?1, ?2, ?3)",
+        params![url, html, fetched_at.to_rfc3339()],
+    )?;
     Ok(())
 }
 
+pub fn get_cached_extract(
+    conn: &Connection,
+    url: &str,
+    processor_key: &str,
+    fresh_after: DateTime<Utc>,
+) -> Result<Option<FetchedContent>> {
+    let mut stmt = conn.prepare(
+        "SELECT title, content, canonical_url, byline, excerpt, lead_image, step FROM extract_cache
+         WHERE url = ?1 AND processor_key = ?2 AND created_at >= ?3",
+    )?;
+    let mut iter = stmt.query_map(params![url, processor_key, fresh_after.to_rfc3339()], |row| {
+        Ok(FetchedContent {
+            title: row.get(0)?,
+            content: row.get(1)?,
+            canonical_url: row.get(2)?,
+            byline: row.get(3)?,
+            excerpt: row.get(4)?,
+            lead_image: row.get(5)?,
+            step: row.get::<_, Option<i32>>(6)?.map(ProcessorType::from_i32).unwrap_or_default(),
+            retries: 0,
+        })
+    })?;
+    iter.next().transpose()
+}
+
+pub fn save_cached_extract(
+    conn: &Connection,
+    url: &str,
+    processor_key: &str,
+    fetched: &FetchedContent,
+    created_at: DateTime<Utc>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO extract_cache (url, processor_key, title, content, canonical_url, created_at,
+                                               byline, excerpt, lead_image, step)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            url,
+            processor_key,
+            fetched.title,
+            fetched.content,
+            fetched.canonical_url,
+            created_at.to_rfc3339(),
+            fetched.byline,
+            fetched.excerpt,
+            fetched.lead_image,
+            fetched.step.to_i32()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Removes cached pages and extractions older than `older_than`, or all of them. Returns the rows removed.
+pub fn purge_content_cache(conn: &Connection, older_than: Option<DateTime<Utc>>) -> Result<usize> {
+    let cutoff = older_than.map(|t| t.to_rfc3339());
+    let pages = conn.execute(
+        "DELETE FROM page_cache WHERE ?1 IS NULL OR fetched_at < ?1",
+        params![cutoff],
+    )?;
+    let extracts = conn.execute(
+        "DELETE FROM extract_cache WHERE ?1 IS NULL OR created_at < ?1",
+        params![cutoff],
+    )?;
+    Ok(pages + extracts)
+}
+
 pub fn add_schedule(conn: &Connection, cron_expression: &str, schedule_type: &str) -> Result<()> {
     conn.execute(
         "INSERT INTO schedules (cron_expression, active, schedule_type, created_at) VALUES (?1, ?2, ?3, ?4)",
@@ -130,7 +599,7 @@ pub fn save_email_config(conn: &Connection, config: &EmailConfig) -> Result<()>
 pub fn add_read_it_later_article(conn: &Connection, url: &str) -> Result<()> {
     conn.execute(
         "INSERT OR IGNORE INTO read_it_later (url, created_at) VALUES (?1, ?2)",
-        params![url, Utc::now().to_rfc3339()],
+        params![crate::util::canonicalize_url(url), Utc::now().to_rfc3339()],
     )?;
     Ok(())
 }
@@ -191,35 +660,88 @@ pub fn mark_articles_as_read(conn: &Connection, ids: &[i64]) -> Result<()> {
 }
 
 pub fn get_general_config(conn: &Connection) -> Result<GeneralConfig> {
-    let mut stmt = conn.prepare("SELECT fetch_since_hours, image_timeout_seconds FROM general_config WHERE id = 1")?;
+    let mut stmt = conn.prepare(
+        "SELECT fetch_since_hours, image_timeout_seconds, feed_concurrency, feed_concurrency_per_host,
+                fetch_mode, auto_disable_after_failures, proxy_url, proxy_bypass,
+                retry_attempts, retry_base_delay_ms, retry_max_delay_ms, user_agent, accept_language,
+                connect_timeout_seconds, feed_timeout_seconds, article_timeout_seconds, max_redirects,
+                digest_layout, article_order, poll_interval_minutes, cache_ttl_hours, read_timeout_seconds
+         FROM general_config WHERE id = 1",
+    )?;
     let mut config_iter = stmt.query_map([], |row| {
         Ok(GeneralConfig {
             fetch_since_hours: row.get(0)?,
             image_timeout_seconds: row.get(1)?,
+            feed_concurrency: row.get(2)?,
+            feed_concurrency_per_host: row.get(3)?,
+            fetch_mode: FetchMode::from_i32(row.get(4)?),
+            auto_disable_after_failures: row.get(5)?,
+            proxy_url: row.get(6)?,
+            proxy_bypass: row.get(7)?,
+            retry_attempts: row.get(8)?,
+            retry_base_delay_ms: row.get(9)?,
+            retry_max_delay_ms: row.get(10)?,
+            user_agent: row.get(11)?,
+            accept_language: row.get(12)?,
+            connect_timeout_seconds: row.get(13)?,
+            feed_timeout_seconds: row.get(14)?,
+            article_timeout_seconds: row.get(15)?,
+            max_redirects: row.get(16)?,
+            digest_layout: DigestLayout::from_i32(row.get(17)?),
+            article_order: ArticleOrder::from_i32(row.get(18)?),
+            poll_interval_minutes: row.get(19)?,
+            cache_ttl_hours: row.get(20)?,
+            read_timeout_seconds: row.get(21)?,
         })
     })?;
 
     if let Some(config) = config_iter.next() {
         Ok(config?)
     } else {
-        Ok(GeneralConfig {
-            fetch_since_hours: 24,
-            image_timeout_seconds: 45,
-        })
+        Ok(GeneralConfig::default())
     }
 }
 
 pub fn update_general_config(conn: &Connection, config: &GeneralConfig) -> Result<()> {
     conn.execute(
-        "INSERT OR REPLACE INTO general_config (id, fetch_since_hours, image_timeout_seconds) VALUES (1, ?1, ?2)",
-        params![config.fetch_since_hours, config.image_timeout_seconds],
+        "INSERT OR REPLACE INTO general_config (id, fetch_since_hours, image_timeout_seconds, feed_concurrency, feed_concurrency_per_host,
+                                                fetch_mode, auto_disable_after_failures, proxy_url, proxy_bypass,
+                                                retry_attempts, retry_base_delay_ms, retry_max_delay_ms, user_agent, accept_language,
+                                                connect_timeout_seconds, feed_timeout_seconds, article_timeout_seconds, max_redirects,
+                                                digest_layout, article_order, poll_interval_minutes, cache_ttl_hours,
+                                                read_timeout_seconds)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+        params![
+            config.fetch_since_hours,
+            config.image_timeout_seconds,
+            config.feed_concurrency,
+            config.feed_concurrency_per_host,
+            config.fetch_mode.to_i32(),
+            config.auto_disable_after_failures,
+            config.proxy_url,
+            config.proxy_bypass,
+            config.retry_attempts,
+            config.retry_base_delay_ms,
+            config.retry_max_delay_ms,
+            config.user_agent,
+            config.accept_language,
+            config.connect_timeout_seconds,
+            config.feed_timeout_seconds,
+            config.article_timeout_seconds,
+            config.max_redirects,
+            config.digest_layout.to_i32(),
+            config.article_order.to_i32(),
+            config.poll_interval_minutes,
+            config.cache_ttl_hours,
+            config.read_timeout_seconds
+        ],
     )?;
     Ok(())
 }
 
 pub fn get_feed_processor(conn: &Connection, feed_id: i64) -> Result<Option<ContentProcessor>> {
     let mut stmt = conn.prepare(
-        "SELECT feed_id, processor, custom_config FROM feed_processor WHERE feed_id = ?1",
+        "SELECT feed_id, processor, custom_config, fallbacks FROM feed_processor WHERE feed_id = ?1",
     )?;
     let mut iter = stmt.query_map(params![feed_id], |row| {
         let processor_int: i32 = row.get(1)?;
@@ -227,6 +749,7 @@ pub fn get_feed_processor(conn: &Connection, feed_id: i64) -> Result<Option<Cont
             id: Some(row.get(0)?),
             processor: ProcessorType::from_i32(processor_int),
             custom_config: row.get(2)?,
+            fallbacks: parse_fallbacks(row.get(3)?),
         })
     })?;
 
@@ -242,34 +765,136 @@ pub fn save_feed_processor(
     feed_id: i64,
     processor: ProcessorType,
     custom_config: Option<&str>,
+    fallbacks: &[ProcessorType],
 ) -> Result<()> {
     conn.execute(
-        "INSERT OR REPLACE INTO feed_processor (feed_id, processor, custom_config) VALUES (?1, ?2, ?3)",
-        params![feed_id, processor.to_i32(), custom_config],
+        "INSERT OR REPLACE INTO feed_processor (feed_id, processor, custom_config, fallbacks) VALUES (?1, ?2, ?3, ?4)",
+        params![feed_id, processor.to_i32(), custom_config, format_fallbacks(fallbacks)],
     )?;
     Ok(())
 }
 
+/// Fallback processors are stored as their comma-separated integer codes.
+fn format_fallbacks(fallbacks: &[ProcessorType]) -> Option<String> {
+    (!fallbacks.is_empty()).then(|| {
+        fallbacks
+            .iter()
+            .map(|p| p.to_i32().to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    })
+}
+
+fn parse_fallbacks(fallbacks: Option<String>) -> Vec<ProcessorType> {
+    fallbacks
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|code| code.trim().parse().ok())
+        .map(ProcessorType::from_i32)
+        .collect()
+}
+
 pub fn delete_feed_processor(conn: &Connection, feed_id: i64) -> Result<()> {
     conn.execute("DELETE FROM feed_processor WHERE feed_id = ?1", params![feed_id])?;
     Ok(())
 }
 
+fn parse_feed_filter(rules: Option<String>) -> Option<FeedFilter> {
+    rules.and_then(|r| serde_json::from_str(&r).ok())
+}
+
+pub fn get_feed_filter(conn: &Connection, feed_id: i64) -> Result<Option<FeedFilter>> {
+    let mut stmt = conn.prepare("SELECT rules FROM feed_filter WHERE feed_id = ?1")?;
+    let mut iter = stmt.query_map(params![feed_id], |row| row.get::<_, String>(0))?;
+
+    if let Some(rules) = iter.next() {
+        Ok(parse_feed_filter(Some(rules?)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn save_feed_filter(conn: &Connection, feed_id: i64, filter: &FeedFilter) -> Result<()> {
+    let rules = serde_json::to_string(filter)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO feed_filter (feed_id, rules) VALUES (?1, ?2)",
+        params![feed_id, rules],
+    )?;
+    Ok(())
+}
+
+pub fn delete_feed_filter(conn: &Connection, feed_id: i64) -> Result<()> {
+    conn.execute("DELETE FROM feed_filter WHERE feed_id = ?1", params![feed_id])?;
+    Ok(())
+}
+
+fn feed_auth_from_row(row: &rusqlite::Row, start: usize) -> Result<FeedAuth> {
+    let headers: Option<String> = row.get(start + 3)?;
+    Ok(FeedAuth {
+        username: row.get(start)?,
+        password: row.get(start + 1)?,
+        bearer_token: row.get(start + 2)?,
+        headers: headers
+            .and_then(|h| serde_json::from_str(&h).ok())
+            .unwrap_or_default(),
+    })
+}
+
+pub fn get_feed_auth(conn: &Connection, feed_id: i64) -> Result<Option<FeedAuth>> {
+    let mut stmt = conn.prepare(
+        "SELECT username, password, bearer_token, headers FROM feed_auth WHERE feed_id = ?1",
+    )?;
+    let mut iter = stmt.query_map(params![feed_id], |row| feed_auth_from_row(row, 0))?;
+
+    if let Some(auth) = iter.next() {
+        Ok(Some(auth?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn save_feed_auth(conn: &Connection, feed_id: i64, auth: &FeedAuth) -> Result<()> {
+    let headers = serde_json::to_string(&auth.headers)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO feed_auth (feed_id, username, password, bearer_token, headers)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![feed_id, auth.username, auth.password, auth.bearer_token, headers],
+    )?;
+    Ok(())
+}
+
+pub fn delete_feed_auth(conn: &Connection, feed_id: i64) -> Result<()> {
+    conn.execute("DELETE FROM feed_auth WHERE feed_id = ?1", params![feed_id])?;
+    Ok(())
+}
+
 pub fn add_domain_override(
     conn: &Connection,
     domain: &str,
     processor: ProcessorType,
     custom_config: Option<&str>,
+    proxy_url: Option<&str>,
+    fallbacks: &[ProcessorType],
 ) -> Result<i64> {
     conn.execute(
-        "INSERT OR REPLACE INTO domain_override (domain, processor, custom_config, created_at) VALUES (?1, ?2, ?3, ?4)",
-        params![domain.to_lowercase(), processor.to_i32(), custom_config, Utc::now().to_rfc3339()],
+        "INSERT OR REPLACE INTO domain_override (domain, processor, custom_config, created_at, proxy_url, fallbacks)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            domain.to_lowercase(),
+            processor.to_i32(),
+            custom_config,
+            Utc::now().to_rfc3339(),
+            proxy_url,
+            format_fallbacks(fallbacks)
+        ],
     )?;
     Ok(conn.last_insert_rowid())
 }
 
 pub fn get_domain_overrides(conn: &Connection) -> Result<Vec<DomainOverride>> {
-    let mut stmt = conn.prepare("SELECT id, domain, processor, custom_config, created_at FROM domain_override ORDER BY created_at DESC")?;
+    let mut stmt = conn.prepare("SELECT id, domain, processor, custom_config, created_at, proxy_url, fallbacks FROM domain_override ORDER BY created_at DESC")?;
     let iter = stmt.query_map([], |row| {
         let processor_int: i32 = row.get(2)?;
         Ok(DomainOverride {
@@ -278,6 +903,8 @@ pub fn get_domain_overrides(conn: &Connection) -> Result<Vec<DomainOverride>> {
             processor: ProcessorType::from_i32(processor_int),
             custom_config: row.get(3)?,
             created_at: row.get(4)?,
+            proxy_url: row.get(5)?,
+            fallbacks: parse_fallbacks(row.get(6)?),
         })
     })?;
 
@@ -293,6 +920,50 @@ pub fn delete_domain_override(conn: &Connection, id: i64) -> Result<()> {
     Ok(())
 }
 
+pub fn save_domain_cookies(conn: &Connection, domain: &str, cookies: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT OR REPLACE INTO domain_cookie (domain, cookies, created_at) VALUES (?1, ?2, ?3)",
+        params![domain.to_lowercase(), cookies, Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Returns `(domain, cookies.txt contents)` for every uploaded jar.
+pub fn get_domain_cookie_jars(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT domain, cookies FROM domain_cookie")?;
+    let iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut jars = Vec::new();
+    for item in iter {
+        jars.push(item?);
+    }
+    Ok(jars)
+}
+
+pub fn get_domain_cookies(conn: &Connection) -> Result<Vec<DomainCookies>> {
+    let mut stmt = conn.prepare("SELECT id, domain, cookies, created_at FROM domain_cookie ORDER BY created_at DESC")?;
+    let iter = stmt.query_map([], |row| {
+        let cookies: String = row.get(2)?;
+        Ok(DomainCookies {
+            id: Some(row.get(0)?),
+            domain: row.get(1)?,
+            cookie_count: crate::util::cookies::parse_netscape_cookies(&cookies).len(),
+            created_at: row.get(3)?,
+        })
+    })?;
+
+    let mut jars = Vec::new();
+    for item in iter {
+        jars.push(item?);
+    }
+    Ok(jars)
+}
+
+pub fn delete_domain_cookies(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM domain_cookie WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,7 +973,27 @@ mod tests {
             "CREATE TABLE IF NOT EXISTS general_config (
                 id INTEGER PRIMARY KEY CHECK (id = 1),
                 fetch_since_hours INTEGER NOT NULL DEFAULT 24,
-                image_timeout_seconds INTEGER NOT NULL DEFAULT 45
+                image_timeout_seconds INTEGER NOT NULL DEFAULT 45,
+                feed_concurrency INTEGER NOT NULL DEFAULT 8,
+                feed_concurrency_per_host INTEGER NOT NULL DEFAULT 2,
+                fetch_mode INTEGER NOT NULL DEFAULT 1,
+                auto_disable_after_failures INTEGER NOT NULL DEFAULT 0,
+                proxy_url TEXT,
+                proxy_bypass TEXT,
+                retry_attempts INTEGER NOT NULL DEFAULT 3,
+                retry_base_delay_ms INTEGER NOT NULL DEFAULT 500,
+                retry_max_delay_ms INTEGER NOT NULL DEFAULT 30000,
+                user_agent TEXT,
+                accept_language TEXT,
+                connect_timeout_seconds INTEGER NOT NULL DEFAULT 10,
+                feed_timeout_seconds INTEGER NOT NULL DEFAULT 45,
+                article_timeout_seconds INTEGER NOT NULL DEFAULT 45,
+                max_redirects INTEGER NOT NULL DEFAULT 10,
+                digest_layout INTEGER NOT NULL DEFAULT 1,
+                article_order INTEGER NOT NULL DEFAULT 1,
+                poll_interval_minutes INTEGER NOT NULL DEFAULT 0,
+                cache_ttl_hours INTEGER NOT NULL DEFAULT 24,
+                read_timeout_seconds INTEGER NOT NULL DEFAULT 30
             )",
             [],
         ).unwrap();
@@ -317,6 +1008,10 @@ mod tests {
         let new_config = GeneralConfig {
             fetch_since_hours: 48,
             image_timeout_seconds: 60,
+            feed_concurrency: 4,
+            digest_layout: DigestLayout::ByCategory,
+            read_timeout_seconds: 15,
+            ..GeneralConfig::default()
         };
 
         update_general_config(&conn, &new_config).unwrap();
@@ -324,11 +1019,15 @@ mod tests {
         let fetched_config = get_general_config(&conn).unwrap();
         assert_eq!(fetched_config.fetch_since_hours, 48);
         assert_eq!(fetched_config.image_timeout_seconds, 60);
+        assert_eq!(fetched_config.feed_concurrency, 4);
+        assert_eq!(fetched_config.digest_layout, DigestLayout::ByCategory);
+        assert_eq!(fetched_config.read_timeout_seconds, 15);
 
         // Update again
         let updated_config = GeneralConfig {
             fetch_since_hours: 12,
             image_timeout_seconds: 30,
+            ..GeneralConfig::default()
         };
         update_general_config(&conn, &updated_config).unwrap();
 
@@ -336,5 +1035,120 @@ mod tests {
         assert_eq!(fetched_config_2.fetch_since_hours, 12);
         assert_eq!(fetched_config_2.image_timeout_seconds, 30);
     }
-}
 
+    #[test]
+    fn test_stored_articles_until_delivered() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let feed_id = add_feed(&conn, "https://example.com/rss", Some("Example"), 0, None, None, Some("Tech")).unwrap();
+        let article = Article {
+            title: "Post".to_string(),
+            link: "https://example.com/post".to_string(),
+            content: "<p>Body</p>".to_string(),
+            pub_date: Utc::now(),
+            source: "Example".to_string(),
+            category: None,
+            priority: 0,
+            feed_id: Some(feed_id),
+            item_key: Some("post-1".to_string()),
+            duplicate_keys: Vec::new(),
+            author: Some("Jane Doe".to_string()),
+            tags: vec!["rust".to_string(), "async".to_string()],
+            excerpt: None,
+            lead_image: None,
+            canonical_url: Some("https://example.com/canonical-post".to_string()),
+            retries: 0,
+        };
+
+        let articles = vec![article];
+        assert_eq!(store_articles(&conn, &articles, Utc::now()).unwrap(), 1);
+        assert_eq!(store_articles(&conn, &articles, Utc::now()).unwrap(), 0);
+        assert!(get_stored_item_keys(&conn, feed_id).unwrap().contains("post-1"));
+
+        let pending = get_undelivered_articles(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].category.as_deref(), Some("Tech"));
+        assert_eq!(pending[0].author.as_deref(), Some("Jane Doe"));
+        assert_eq!(pending[0].tags, vec!["rust", "async"]);
+        assert_eq!(pending[0].canonical_url.as_deref(), Some("https://example.com/canonical-post"));
+
+        mark_stored_articles_delivered(&conn, &[(feed_id, "post-1".to_string())], Utc::now()).unwrap();
+        assert!(get_undelivered_articles(&conn).unwrap().is_empty());
+        assert_eq!(prune_stored_articles(&conn, Utc::now() - chrono::Duration::hours(1)).unwrap(), 0);
+        assert_eq!(prune_stored_articles(&conn, Utc::now() + chrono::Duration::hours(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_content_cache_freshness_and_purge() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let url = "https://example.com/post";
+        let fetched_at = Utc::now() - chrono::Duration::hours(2);
+        save_cached_page(&conn, url, "<html></html>", fetched_at).unwrap();
+        let extracted = FetchedContent {
+            title: "Post".to_string(),
+            content: "<p>Body</p>".to_string(),
+            canonical_url: None,
+            byline: None,
+            excerpt: None,
+            lead_image: None,
+            step: ProcessorType::Default,
+            retries: 0,
+        };
+        save_cached_extract(&conn, url, "abc", &extracted, fetched_at).unwrap();
+
+        let hour_ago = Utc::now() - chrono::Duration::hours(1);
+        let day_ago = Utc::now() - chrono::Duration::hours(24);
+        assert_eq!(get_cached_page(&conn, url, hour_ago).unwrap(), None);
+        assert_eq!(get_cached_page(&conn, url, day_ago).unwrap().as_deref(), Some("<html></html>"));
+        assert!(get_cached_extract(&conn, url, "other", day_ago).unwrap().is_none());
+        assert_eq!(get_cached_extract(&conn, url, "abc", day_ago).unwrap().unwrap().content, "<p>Body</p>");
+
+        assert_eq!(purge_content_cache(&conn, Some(day_ago)).unwrap(), 0);
+        assert_eq!(purge_content_cache(&conn, None).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_update_feed_partial() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let feed_id = add_feed(&conn, "https://example.com/rss", Some("Example"), 0, None, None, Some("Tech")).unwrap();
+        update_feed_category(&conn, feed_id, Some("Tech")).unwrap();
+
+        let rename = UpdateFeedRequest { name: Some(Some("Renamed".to_string())), ..Default::default() };
+        assert_eq!(update_feed(&conn, feed_id, &rename).unwrap(), 1);
+        let feed = get_feeds(&conn).unwrap().remove(0);
+        assert_eq!(feed.name.as_deref(), Some("Renamed"));
+        assert_eq!(feed.category.as_deref(), Some("Tech"));
+
+        let clear = UpdateFeedRequest { category: Some(None), ..Default::default() };
+        update_feed(&conn, feed_id, &clear).unwrap();
+        assert_eq!(get_feeds(&conn).unwrap().remove(0).category, None);
+
+        assert_eq!(update_feed(&conn, feed_id + 1, &UpdateFeedRequest::default()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reorder_feeds_requires_every_feed_once() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let a = add_feed(&conn, "https://a.example/rss", None, 0, None, None, None).unwrap();
+        let b = add_feed(&conn, "https://b.example/rss", None, 0, None, None, None).unwrap();
+
+        assert!(!reorder_feeds(&conn, &[b]).unwrap());
+        assert!(!reorder_feeds(&conn, &[b, b]).unwrap());
+        assert!(!reorder_feeds(&conn, &[b, a, 99]).unwrap());
+        assert!(reorder_feeds(&conn, &[b, a]).unwrap());
+        let order: Vec<i64> = get_feeds(&conn).unwrap().iter().filter_map(|f| f.id).collect();
+        assert_eq!(order, vec![b, a]);
+    }
+
+    #[test]
+    fn test_article_and_image_retries_add_to_feed_health() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let feed_id = add_feed(&conn, "https://example.com/rss", None, 0, None, None, None).unwrap();
+
+        record_feed_fetch(&conn, feed_id, Some(200), None, 2, 0).unwrap();
+        add_feed_retries(&conn, feed_id, 3).unwrap();
+        assert_eq!(get_feeds(&conn).unwrap().remove(0).health.last_retry_count, 5);
+
+        record_feed_fetch(&conn, feed_id, Some(200), None, 0, 0).unwrap();
+        assert_eq!(get_feeds(&conn).unwrap().remove(0).health.last_retry_count, 0);
+    }
+}
@@ -1,4 +1,4 @@
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
 
 pub fn init_db(path: &str) -> rusqlite::Result<Connection> {
     let conn = Connection::open(path)?;
@@ -14,6 +14,23 @@ pub fn init_db(path: &str) -> rusqlite::Result<Connection> {
         [],
     )?;
 
+    add_column_if_missing(&conn, "feeds", "etag", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "last_modified", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "last_delivered_at", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "fetch_since_hours", "INTEGER")?;
+    add_column_if_missing(&conn, "feeds", "max_items", "INTEGER")?;
+    add_column_if_missing(&conn, "feeds", "enabled", "BOOLEAN NOT NULL DEFAULT 1")?;
+    add_column_if_missing(&conn, "feeds", "last_attempt_at", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "last_success_at", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "consecutive_failures", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "feeds", "last_http_status", "INTEGER")?;
+    add_column_if_missing(&conn, "feeds", "last_error", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "proxy_url", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "last_retry_count", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "feeds", "category", "TEXT")?;
+    add_column_if_missing(&conn, "feeds", "priority", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "feeds", "poll_interval_minutes", "INTEGER")?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS schedules (
             id INTEGER PRIMARY KEY,
@@ -73,6 +90,27 @@ pub fn init_db(path: &str) -> rusqlite::Result<Connection> {
         [],
     )?;
 
+    add_column_if_missing(&conn, "general_config", "feed_concurrency", "INTEGER NOT NULL DEFAULT 8")?;
+    add_column_if_missing(&conn, "general_config", "feed_concurrency_per_host", "INTEGER NOT NULL DEFAULT 2")?;
+    add_column_if_missing(&conn, "general_config", "fetch_mode", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(&conn, "general_config", "auto_disable_after_failures", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "general_config", "proxy_url", "TEXT")?;
+    add_column_if_missing(&conn, "general_config", "proxy_bypass", "TEXT")?;
+    add_column_if_missing(&conn, "general_config", "retry_attempts", "INTEGER NOT NULL DEFAULT 3")?;
+    add_column_if_missing(&conn, "general_config", "retry_base_delay_ms", "INTEGER NOT NULL DEFAULT 500")?;
+    add_column_if_missing(&conn, "general_config", "retry_max_delay_ms", "INTEGER NOT NULL DEFAULT 30000")?;
+    add_column_if_missing(&conn, "general_config", "user_agent", "TEXT")?;
+    add_column_if_missing(&conn, "general_config", "accept_language", "TEXT")?;
+    add_column_if_missing(&conn, "general_config", "connect_timeout_seconds", "INTEGER NOT NULL DEFAULT 10")?;
+    add_column_if_missing(&conn, "general_config", "read_timeout_seconds", "INTEGER NOT NULL DEFAULT 30")?;
+    add_column_if_missing(&conn, "general_config", "feed_timeout_seconds", "INTEGER NOT NULL DEFAULT 45")?;
+    add_column_if_missing(&conn, "general_config", "article_timeout_seconds", "INTEGER NOT NULL DEFAULT 45")?;
+    add_column_if_missing(&conn, "general_config", "max_redirects", "INTEGER NOT NULL DEFAULT 10")?;
+    add_column_if_missing(&conn, "general_config", "digest_layout", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(&conn, "general_config", "article_order", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(&conn, "general_config", "poll_interval_minutes", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "general_config", "cache_ttl_hours", "INTEGER NOT NULL DEFAULT 24")?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS feed_processor (
             feed_id INTEGER PRIMARY KEY,
@@ -109,6 +147,29 @@ pub fn init_db(path: &str) -> rusqlite::Result<Connection> {
         )?;
     }
 
+    add_column_if_missing(&conn, "feed_processor", "fallbacks", "TEXT")?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS feed_filter (
+            feed_id INTEGER PRIMARY KEY,
+            rules TEXT NOT NULL,
+            FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS feed_auth (
+            feed_id INTEGER PRIMARY KEY,
+            username TEXT,
+            password TEXT,
+            bearer_token TEXT,
+            headers TEXT,
+            FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS domain_override (
             id INTEGER PRIMARY KEY,
@@ -119,6 +180,111 @@ pub fn init_db(path: &str) -> rusqlite::Result<Connection> {
         )",
         [],
     )?;
+    add_column_if_missing(&conn, "domain_override", "proxy_url", "TEXT")?;
+    add_column_if_missing(&conn, "domain_override", "fallbacks", "TEXT")?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS domain_cookie (
+            id INTEGER PRIMARY KEY,
+            domain TEXT NOT NULL UNIQUE,
+            cookies TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS seen_items (
+            feed_id INTEGER NOT NULL,
+            item_key TEXT NOT NULL,
+            delivered_at TEXT NOT NULL,
+            PRIMARY KEY (feed_id, item_key),
+            FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS item_first_seen (
+            feed_id INTEGER NOT NULL,
+            item_key TEXT NOT NULL,
+            first_seen_at TEXT NOT NULL,
+            PRIMARY KEY (feed_id, item_key),
+            FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stored_articles (
+            feed_id INTEGER NOT NULL,
+            item_key TEXT NOT NULL,
+            title TEXT NOT NULL,
+            link TEXT NOT NULL,
+            content TEXT NOT NULL,
+            pub_date TEXT NOT NULL,
+            source TEXT NOT NULL,
+            fetched_at TEXT NOT NULL,
+            delivered_at TEXT,
+            PRIMARY KEY (feed_id, item_key),
+            FOREIGN KEY (feed_id) REFERENCES feeds(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    add_column_if_missing(&conn, "stored_articles", "author", "TEXT")?;
+    add_column_if_missing(&conn, "stored_articles", "tags", "TEXT")?;
+    add_column_if_missing(&conn, "stored_articles", "excerpt", "TEXT")?;
+    add_column_if_missing(&conn, "stored_articles", "lead_image", "TEXT")?;
+    add_column_if_missing(&conn, "stored_articles", "canonical_url", "TEXT")?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS page_cache (
+            url TEXT PRIMARY KEY,
+            html TEXT NOT NULL,
+            fetched_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS extract_cache (
+            url TEXT NOT NULL,
+            processor_key TEXT NOT NULL,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            canonical_url TEXT,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (url, processor_key)
+        )",
+        [],
+    )?;
+    add_column_if_missing(&conn, "extract_cache", "byline", "TEXT")?;
+    add_column_if_missing(&conn, "extract_cache", "excerpt", "TEXT")?;
+    add_column_if_missing(&conn, "extract_cache", "lead_image", "TEXT")?;
+    add_column_if_missing(&conn, "extract_cache", "step", "INTEGER")?;
 
     Ok(conn)
 }
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let count: i32 = conn
+        .query_row(
+            &format!("SELECT count(*) FROM pragma_table_info('{}') WHERE name = ?1", table),
+            params![column],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    if count == 0 {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::models::{DiscoverFeedResponse, FeedCandidate};
+use crate::util::http::{self, RequestKind};
+use dom_query::Document;
+use feed_rs::parser;
+use reqwest::Client;
+use tokio::task::JoinSet;
+use tracing::info;
+use url::Url;
+
+const COMMON_FEED_PATHS: &[&str] = &[
+    "/feed",
+    "/rss",
+    "/feed.xml",
+    "/rss.xml",
+    "/atom.xml",
+    "/index.xml",
+    "/feed/atom",
+];
+
+/// Resolves `url` to a feed: either the URL itself, the feeds advertised by the page,
+/// or feeds found at well-known paths on the same site.
+pub async fn discover_feeds(url: &str) -> anyhow::Result<DiscoverFeedResponse> {
+    let client = http::client_for(url, None)?;
+
+    let resp = client
+        .get(url)
+        .timeout(http::timeout_for(RequestKind::Feed))
+        .send()
+        .await?
+        .error_for_status()?;
+    let base = resp.url().clone();
+    let body = resp.bytes().await?;
+
+    if let Ok(feed) = parser::parse(&body[..]) {
+        info!("URL is already a feed: {}", base);
+        return Ok(resolved(vec![FeedCandidate {
+            url: base.to_string(),
+            title: feed.title.map(|t| t.content),
+            kind: feed_kind(&feed.feed_type).to_string(),
+        }]));
+    }
+
+    let html = String::from_utf8_lossy(&body);
+    let mut candidates = find_feed_links(&html, &base);
+    if candidates.is_empty() {
+        info!("No advertised feeds on {}, probing common paths", base);
+        candidates = probe_common_paths(&client, &base).await;
+    }
+
+    Ok(resolved(candidates))
+}
+
+fn resolved(candidates: Vec<FeedCandidate>) -> DiscoverFeedResponse {
+    let feed_url = if candidates.len() == 1 {
+        Some(candidates[0].url.clone())
+    } else {
+        None
+    };
+    DiscoverFeedResponse { feed_url, candidates }
+}
+
+/// Collects `<link rel="alternate">` feed declarations, resolved against `base`.
+pub fn find_feed_links(html: &str, base: &Url) -> Vec<FeedCandidate> {
+    let document = Document::from(html);
+    let mut candidates: Vec<FeedCandidate> = Vec::new();
+
+    for node in document.select("link[rel~='alternate'][href]").nodes() {
+        let link_type = node.attr("type").map(|t| t.to_lowercase()).unwrap_or_default();
+        let kind = if link_type.contains("rss+xml") {
+            "rss"
+        } else if link_type.contains("atom+xml") {
+            "atom"
+        } else {
+            continue;
+        };
+        let Some(href) = node.attr("href") else { continue };
+        let Ok(feed_url) = base.join(href.trim()) else { continue };
+        let feed_url = feed_url.to_string();
+        if candidates.iter().any(|c| c.url == feed_url) {
+            continue;
+        }
+        candidates.push(FeedCandidate {
+            url: feed_url,
+            title: node.attr("title").map(|t| t.to_string()).filter(|t| !t.is_empty()),
+            kind: kind.to_string(),
+        });
+    }
+
+    candidates
+}
+
+async fn probe_common_paths(client: &Client, base: &Url) -> Vec<FeedCandidate> {
+    let mut join_set = JoinSet::new();
+    for (index, path) in COMMON_FEED_PATHS.iter().enumerate() {
+        let Ok(probe_url) = base.join(path) else { continue };
+        let client = client.clone();
+        join_set.spawn(async move {
+            let resp = client
+                .get(probe_url.as_str())
+                .timeout(http::timeout_for(RequestKind::Feed))
+                .send()
+                .await
+                .ok()?;
+            if !resp.status().is_success() {
+                return None;
+            }
+            let final_url = resp.url().to_string();
+            let body = resp.bytes().await.ok()?;
+            let feed = parser::parse(&body[..]).ok()?;
+            Some((
+                index,
+                FeedCandidate {
+                    url: final_url,
+                    title: feed.title.map(|t| t.content),
+                    kind: feed_kind(&feed.feed_type).to_string(),
+                },
+            ))
+        });
+    }
+
+    let mut found = Vec::new();
+    while let Some(res) = join_set.join_next().await {
+        if let Ok(Some(candidate)) = res {
+            found.push(candidate);
+        }
+    }
+    found.sort_by_key(|(index, _)| *index);
+
+    // Several paths often redirect to the same feed.
+    let mut candidates: Vec<FeedCandidate> = Vec::new();
+    for (_, candidate) in found {
+        if !candidates.iter().any(|c| c.url == candidate.url) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+fn feed_kind(feed_type: &feed_rs::model::FeedType) -> &'static str {
+    match feed_type {
+        feed_rs::model::FeedType::Atom => "atom",
+        feed_rs::model::FeedType::JSON => "json",
+        _ => "rss",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_feed_links() {
+        let html = r#"<html><head>
+            <link rel="alternate" type="application/rss+xml" title="Posts" href="/feed.xml">
+            <link rel="alternate" type="application/atom+xml" href="https://cdn.example.com/atom.xml">
+            <link rel="alternate" type="text/html" hreflang="de" href="/de/">
+            <link rel="stylesheet" href="/style.css">
+            <link rel="alternate" type="application/rss+xml" href="feed.xml">
+        </head><body></body></html>"#;
+        let base = Url::parse("https://example.com/blog/").unwrap();
+
+        let candidates = find_feed_links(html, &base);
+
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].url, "https://example.com/feed.xml");
+        assert_eq!(candidates[0].title.as_deref(), Some("Posts"));
+        assert_eq!(candidates[0].kind, "rss");
+        assert_eq!(candidates[1].url, "https://cdn.example.com/atom.xml");
+        assert_eq!(candidates[1].kind, "atom");
+        assert_eq!(candidates[2].url, "https://example.com/blog/feed.xml");
+    }
+}
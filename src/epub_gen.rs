@@ -1,34 +1,142 @@
 use crate::epub_message::EpubPart;
 use crate::feed::Article;
+use crate::models::{ArticleOrder, DigestLayout};
 use crate::image::process_images;
+use crate::util;
 use anyhow::Result;
 use chrono::Utc;
 use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ReferenceType, ZipLibrary};
 use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Seek, SeekFrom, Write};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::task::JoinSet;
 use tracing::info;
 
-pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
-    articles: &[Article],
-    output: W,
-    image_timeout_seconds: i32,
-) -> Result<()> {
-    use crate::epub_message::{CompletionMessage, EpubPart};
-    use crate::util;
-    use std::collections::HashMap;
-    let mut articles_by_source: HashMap<String, Vec<&Article>> = HashMap::new();
-    for article in articles {
-        articles_by_source
+const UNCATEGORIZED: &str = "Uncategorized";
+const WORDS_PER_MINUTE: usize = 230;
+
+/// A source's contents page and the indices of the articles it lists.
+struct SourceSection {
+    name: String,
+    filename: String,
+    articles: Vec<usize>,
+}
+
+/// A category's contents page; `filename` is `None` when the digest is laid out by source only.
+struct CategorySection {
+    name: String,
+    filename: Option<String>,
+    sources: Vec<SourceSection>,
+}
+
+impl CategorySection {
+    fn article_count(&self) -> usize {
+        self.sources.iter().map(|s| s.articles.len()).sum()
+    }
+}
+
+/// Source names with the indices of their articles.
+type SourceGroups = Vec<(String, Vec<usize>)>;
+
+fn slug(name: &str) -> String {
+    name.replace(|c: char| !c.is_alphanumeric(), "_").to_lowercase()
+}
+
+fn reading_minutes(html: &str) -> usize {
+    let words = dom_query::Document::from(html).select("body").text().split_whitespace().count();
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
+/// Title block at the top of an article chapter.
+fn chapter_header(article: &Article) -> String {
+    let mut details = format!("<strong>Source:</strong> {}", util::escape_xml(&article.source));
+    if let Some(author) = &article.author {
+        details.push_str(&format!(" <br /> <strong>Author:</strong> {}", util::escape_xml(author)));
+    }
+    details.push_str(&format!(
+        " <br /> <strong>Date:</strong> {} <br /> <strong>Reading time:</strong> {} min",
+        article.pub_date.format("%Y-%m-%d %H:%M"),
+        reading_minutes(&article.content)
+    ));
+    if !article.tags.is_empty() {
+        details.push_str(&format!(
+            " <br /> <strong>Tags:</strong> {}",
+            util::escape_xml(&article.tags.join(", "))
+        ));
+    }
+    format!("<h1>{}</h1><p>{}</p><hr />", util::escape_xml(&article.title), details)
+}
+
+/// Groups articles into contents pages. Articles keep their order within a source; sources and
+/// categories are sorted by name, or by feed order when the digest follows it.
+fn build_sections(articles: &[Article], layout: DigestLayout, order: ArticleOrder) -> Vec<CategorySection> {
+    // Uncategorized sorts after every named category.
+    let mut tree: BTreeMap<(bool, String), BTreeMap<String, Vec<usize>>> = BTreeMap::new();
+    for (i, article) in articles.iter().enumerate() {
+        let category = match layout {
+            DigestLayout::BySource => (false, String::new()),
+            DigestLayout::ByCategory => match &article.category {
+                Some(category) => (false, category.clone()),
+                None => (true, UNCATEGORIZED.to_string()),
+            },
+        };
+        tree.entry(category)
+            .or_default()
             .entry(article.source.clone())
             .or_default()
-            .push(article);
+            .push(i);
+    }
+
+    let mut categories: Vec<(String, SourceGroups)> = tree
+        .into_iter()
+        .map(|((_, name), sources)| (name, sources.into_iter().collect()))
+        .collect();
+    if matches!(order, ArticleOrder::FeedPriority | ArticleOrder::RoundRobin) {
+        let first = |indices: &[usize]| indices.iter().map(|&i| articles[i].priority).min().unwrap_or(i64::MAX);
+        for (_, sources) in categories.iter_mut() {
+            sources.sort_by_key(|(_, indices)| first(indices));
+        }
+        categories.sort_by_key(|(_, sources)| sources.iter().map(|(_, indices)| first(indices)).min());
     }
 
-    let mut sources: Vec<_> = articles_by_source.keys().cloned().collect();
-    sources.sort();
+    let by_category = layout == DigestLayout::ByCategory;
+    categories
+        .into_iter()
+        .enumerate()
+        .map(|(ci, (name, sources))| {
+            let sources = sources
+                .into_iter()
+                .enumerate()
+                .map(|(si, (source, articles))| SourceSection {
+                    filename: if by_category {
+                        format!("toc_cat_{}_{}.xhtml", ci, si)
+                    } else {
+                        format!("toc_{}.xhtml", slug(&source))
+                    },
+                    name: source,
+                    articles,
+                })
+                .collect();
+            CategorySection {
+                filename: by_category.then(|| format!("toc_cat_{}.xhtml", ci)),
+                name,
+                sources,
+            }
+        })
+        .collect()
+}
+
+/// Writes the digest and returns the retries its image downloads needed, per feed.
+pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
+    articles: &[Article],
+    output: W,
+    layout: DigestLayout,
+    order: ArticleOrder,
+) -> Result<HashMap<i64, u32>> {
+    use crate::epub_message::{CompletionMessage, EpubPart};
+    let sections = build_sections(articles, layout, order);
 
     let mut article_filenames = HashMap::new();
     for (i, _article) in articles.iter().enumerate() {
@@ -40,18 +148,36 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
     let master_toc_seq_id = 0;
     next_seq_id += 1;
 
+    let mut category_toc_seq_ids = HashMap::new();
     let mut source_toc_seq_ids = HashMap::new();
     let mut article_seq_ids = HashMap::new();
+    let mut article_back_links = HashMap::new();
+    // Round-robin chapters follow all the contents pages in their interleaved order rather than
+    // sitting under their source, so they form a flat list in the navigation.
+    let round_robin = order == ArticleOrder::RoundRobin;
+    let article_level = if layout == DigestLayout::ByCategory && !round_robin { 3 } else { 1 };
+
+    for (ci, category) in sections.iter().enumerate() {
+        if category.filename.is_some() {
+            category_toc_seq_ids.insert(ci, next_seq_id);
+            next_seq_id += 1;
+        }
 
-    for source in &sources {
-        source_toc_seq_ids.insert(source.clone(), next_seq_id);
-        next_seq_id += 1;
+        for (si, source) in category.sources.iter().enumerate() {
+            source_toc_seq_ids.insert((ci, si), next_seq_id);
+            next_seq_id += 1;
 
-        for article in &articles_by_source[source] {
-            let index = articles
-                .iter()
-                .position(|a| std::ptr::eq(a, *article))
-                .unwrap();
+            for &index in &source.articles {
+                article_back_links.insert(index, source.filename.clone());
+                if !round_robin {
+                    article_seq_ids.insert(index, next_seq_id);
+                    next_seq_id += 1;
+                }
+            }
+        }
+    }
+    if round_robin {
+        for index in 0..articles.len() {
             article_seq_ids.insert(index, next_seq_id);
             next_seq_id += 1;
         }
@@ -148,16 +274,24 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
     });
 
     let mut master_toc_html = String::from("<h1>Table of Contents</h1><ul>");
-    for source in &sources {
-        let source_slug = source
-            .replace(|c: char| !c.is_alphanumeric(), "_")
-            .to_lowercase();
-        let source_toc_filename = format!("toc_{}.xhtml", source_slug);
-        master_toc_html.push_str(&format!(
-            "<li><a href=\"{}\">{}</a></li>",
-            source_toc_filename,
-            util::escape_xml(source)
-        ));
+    for category in &sections {
+        match &category.filename {
+            Some(filename) => master_toc_html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> ({})</li>",
+                filename,
+                util::escape_xml(&category.name),
+                category.article_count()
+            )),
+            None => {
+                for source in &category.sources {
+                    master_toc_html.push_str(&format!(
+                        "<li><a href=\"{}\">{}</a></li>",
+                        source.filename,
+                        util::escape_xml(&source.name)
+                    ));
+                }
+            }
+        }
     }
     master_toc_html.push_str("</ul>");
     let master_toc_content =
@@ -170,51 +304,94 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
             title: "Table of Contents".to_string(),
             content: master_toc_content,
             reftype: Some(ReferenceType::Toc),
+            level: 1,
         }],
     })
     .await
     .map_err(|_| anyhow::anyhow!("Failed to send Master TOC"))?;
 
-    for source in &sources {
-        let source_slug = source
-            .replace(|c: char| !c.is_alphanumeric(), "_")
-            .to_lowercase();
-        let source_toc_filename = format!("toc_{}.xhtml", source_slug);
-        let source_articles = &articles_by_source[source];
-
-        let mut source_toc_html = format!(
-            "<h1>{}</h1><p style='text-align: center;'><a href=\"toc.xhtml\">Back to Master TOC</a></p></br><ul>",
-            util::escape_xml(source)
-        );
-        for article in source_articles {
-            let index = articles
-                .iter()
-                .position(|a| std::ptr::eq(a, *article))
-                .unwrap();
-            let filename = &article_filenames[&index];
+    for (ci, category) in sections.iter().enumerate() {
+        if let Some(category_toc_filename) = &category.filename {
+            let mut category_toc_html = format!(
+                "<h1>{}</h1><p style='text-align: center;'><a href=\"toc.xhtml\">Back to Master TOC</a></p></br><ul>",
+                util::escape_xml(&category.name)
+            );
+            for source in &category.sources {
+                category_toc_html.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a> ({} {})</li>",
+                    source.filename,
+                    util::escape_xml(&source.name),
+                    source.articles.len(),
+                    if source.articles.len() == 1 { "article" } else { "articles" }
+                ));
+            }
+            category_toc_html.push_str("</ul></br><p style='text-align: center;'><a href=\"toc.xhtml\">Back to Master TOC</a></p>");
+            let category_toc_content =
+                util::wrap_xhtml(&category.name, &util::fix_xhtml(&category_toc_html));
+
+            tx.send(CompletionMessage {
+                sequence_id: category_toc_seq_ids[&ci],
+                parts: vec![EpubPart::Content {
+                    filename: category_toc_filename.clone(),
+                    title: category.name.clone(),
+                    content: category_toc_content,
+                    reftype: None,
+                    level: 1,
+                }],
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("Failed to send Category TOC"))?;
+        }
+
+        let (back_href, back_label) = match &category.filename {
+            Some(filename) => (filename.clone(), format!("Back to {}", util::escape_xml(&category.name))),
+            None => ("toc.xhtml".to_string(), "Back to Master TOC".to_string()),
+        };
+
+        for (si, source) in category.sources.iter().enumerate() {
+            let mut source_toc_html = format!(
+                "<h1>{}</h1><p style='text-align: center;'><a href=\"{}\">{}</a></p></br><ul>",
+                util::escape_xml(&source.name),
+                back_href,
+                back_label
+            );
+            for &index in &source.articles {
+                let filename = &article_filenames[&index];
+                let excerpt = articles[index]
+                    .excerpt
+                    .as_deref()
+                    .map(|e| format!("<br /><small>{}</small>", util::escape_xml(e)))
+                    .unwrap_or_default();
+                source_toc_html.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a>{}</li>",
+                    filename,
+                    util::escape_xml(&articles[index].title),
+                    excerpt
+                ));
+            }
             source_toc_html.push_str(&format!(
-                "<li><a href=\"{}\">{}</a></li>",
-                filename,
-                util::escape_xml(&article.title)
+                "</ul></br><p style='text-align: center;'><a href=\"{}\">{}</a></p>",
+                back_href, back_label
             ));
+            let source_toc_content =
+                util::wrap_xhtml(&source.name, &util::fix_xhtml(&source_toc_html));
+
+            tx.send(CompletionMessage {
+                sequence_id: source_toc_seq_ids[&(ci, si)],
+                parts: vec![EpubPart::Content {
+                    filename: source.filename.clone(),
+                    title: source.name.clone(),
+                    content: source_toc_content,
+                    reftype: None,
+                    level: if category.filename.is_some() { 2 } else { 1 },
+                }],
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("Failed to send Source TOC"))?;
         }
-        source_toc_html.push_str("</ul></br><p style='text-align: center;'><a href=\"toc.xhtml\">Back to Master TOC</a></p>");
-        let source_toc_content = util::wrap_xhtml(source, &util::fix_xhtml(&source_toc_html));
-
-        let seq_id = source_toc_seq_ids[source];
-        tx.send(CompletionMessage {
-            sequence_id: seq_id,
-            parts: vec![EpubPart::Content {
-                filename: source_toc_filename,
-                title: source.clone(),
-                content: source_toc_content,
-                reftype: None,
-            }],
-        })
-        .await
-        .map_err(|_| anyhow::anyhow!("Failed to send Source TOC"))?;
     }
 
+    let mut image_retries = Vec::new();
     let mut join_set = JoinSet::new();
     for (i, article) in articles.iter().enumerate() {
         let article = article.clone();
@@ -223,23 +400,29 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
         let seq_id = article_seq_ids[&i];
         let tx = tx.clone();
 
-        let source_slug = article
-            .source
-            .replace(|c: char| !c.is_alphanumeric(), "_")
-            .to_lowercase();
-        let back_link = format!("toc_{}.xhtml", source_slug);
+        let back_link = article_back_links[&i].clone();
         let tx_m = tx_m.clone();
         let counter_ref = Arc::clone(&counter);
+        let retries = Arc::new(AtomicU32::new(0));
+        if let Some(feed_id) = article.feed_id {
+            image_retries.push((feed_id, Arc::clone(&retries)));
+        }
         join_set.spawn(async move {
-            let cleaned_content = util::clean_html(&article.content);
-            let (processed_content,total_images_for_seq) = process_images(&cleaned_content,&tx_m,&seq_id, image_timeout_seconds as u64).await;
+            let header = chapter_header(&article);
+            // Readability often drops the hero image; put it back unless the body already shows it.
+            let body = match &article.lead_image {
+                Some(image) if !article.content.contains(image.as_str()) => {
+                    format!("<p><img src=\"{}\" alt=\"\" /></p>{}", util::escape_xml(image), article.content)
+                }
+                _ => article.content.clone(),
+            };
+            let cleaned_content = util::clean_html(&body);
+            let (processed_content,total_images_for_seq) = process_images(&cleaned_content,&tx_m,&seq_id,&retries).await;
             counter_ref.fetch_add(total_images_for_seq, Ordering::Relaxed);
             let fixed_content = util::fix_xhtml(&processed_content);
             let content_html = format!(
-                "<h1>{}</h1><p><strong>Source:</strong> {} <br /> <strong>Date:</strong> {}</p><hr />{}<p><a href=\"{}\">Read original article</a></p><p><a href=\"{}\">Back to Feed TOC</a></p>",
-                util::escape_xml(&article.title),
-                util::escape_xml(&article.source),
-                article.pub_date.format("%Y-%m-%d %H:%M"),
+                "{}{}<p><a href=\"{}\">Read original article</a></p><p><a href=\"{}\">Back to Feed TOC</a></p>",
+                header,
                 fixed_content,
                 util::escape_xml(&article.link),
                 back_link
@@ -253,6 +436,7 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
                 title: article.title,
                 content: final_content,
                 reftype: None,
+                level: article_level,
             });
                 info!("Sending Completed Part {}", temp_log);
             if let Err(_) = tx.send(CompletionMessage {
@@ -276,7 +460,12 @@ pub async fn generate_epub_data<W: Write + Seek + Send + 'static>(
         .map_err(|e| anyhow::anyhow!("Builder task joined error: {}", e))??;
 
     info!("EPUB generated successfully");
-    Ok(())
+    // The builder waits for every image, so each article's tally is final by now.
+    let mut retries_by_feed = HashMap::new();
+    for (feed_id, retries) in image_retries {
+        *retries_by_feed.entry(feed_id).or_insert(0) += retries.load(Ordering::Relaxed);
+    }
+    Ok(retries_by_feed)
 }
 
 fn populate_epub_data(builder: &mut EpubBuilder<ZipLibrary>, parts: Vec<EpubPart>) -> Result<()> {
@@ -287,8 +476,11 @@ fn populate_epub_data(builder: &mut EpubBuilder<ZipLibrary>, parts: Vec<EpubPart
                 title,
                 content,
                 reftype,
+                level,
             } => {
-                let mut content = EpubContent::new(filename, content.as_bytes()).title(title);
+                let mut content = EpubContent::new(filename, content.as_bytes())
+                    .title(title)
+                    .level(level);
                 if let Some(rt) = reftype {
                     content = content.reftype(rt);
                 }
@@ -310,3 +502,70 @@ fn populate_epub_data(builder: &mut EpubBuilder<ZipLibrary>, parts: Vec<EpubPart
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(source: &str, category: Option<&str>) -> Article {
+        Article {
+            title: format!("{} post", source),
+            link: String::new(),
+            content: String::new(),
+            pub_date: Utc::now(),
+            source: source.to_string(),
+            category: category.map(str::to_string),
+            priority: 0,
+            feed_id: None,
+            item_key: None,
+            duplicate_keys: Vec::new(),
+            author: None,
+            tags: Vec::new(),
+            excerpt: None,
+            lead_image: None,
+            canonical_url: None,
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_build_sections_by_category() {
+        let articles = vec![
+            article("Zed Blog", None),
+            article("Rust Blog", Some("Tech")),
+            article("BBC", Some("News")),
+            article("Rust Blog", Some("Tech")),
+            article("Go Blog", Some("Tech")),
+        ];
+
+        let sections = build_sections(&articles, DigestLayout::ByCategory, ArticleOrder::NewestFirst);
+        let names: Vec<&str> = sections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["News", "Tech", UNCATEGORIZED]);
+        let tech = &sections[1];
+        assert_eq!(tech.article_count(), 3);
+        assert_eq!(tech.sources[1].name, "Rust Blog");
+        assert_eq!(tech.sources[1].articles, vec![1, 3]);
+        assert!(tech.filename.is_some());
+
+        let flat = build_sections(&articles, DigestLayout::BySource, ArticleOrder::NewestFirst);
+        assert_eq!(flat.len(), 1);
+        assert!(flat[0].filename.is_none());
+        assert_eq!(flat[0].sources[0].filename, "toc_bbc.xhtml");
+    }
+
+    #[test]
+    fn test_chapter_header() {
+        let mut post = article("Rust Blog", None);
+        post.content = format!("<p>{}</p>", "word ".repeat(500));
+        let header = chapter_header(&post);
+        assert!(header.contains("<strong>Reading time:</strong> 3 min"));
+        assert!(!header.contains("Author"));
+        assert!(!header.contains("Tags"));
+
+        post.author = Some("Jane & John".to_string());
+        post.tags = vec!["rust".to_string(), "release".to_string()];
+        let header = chapter_header(&post);
+        assert!(header.contains("<strong>Author:</strong> Jane &amp; John"));
+        assert!(header.contains("<strong>Tags:</strong> rust, release"));
+    }
+}
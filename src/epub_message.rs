@@ -11,6 +11,8 @@ pub enum EpubPart {
         title: String,
         content: String,
         reftype: Option<ReferenceType>,
+        /// Nesting depth in the EPUB navigation, starting at 1.
+        level: i32,
     },
     Resource {
         filename: String,
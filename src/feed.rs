@@ -1,13 +1,16 @@
 use chrono::{DateTime, Utc};
-use feed_rs::model::Feed;
+use feed_rs::model::{Entry, Feed};
 use feed_rs::parser;
-use reqwest::Client;
+use reqwest::{header, StatusCode};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::sync::Semaphore;
 use tracing::{error, info, warn};
-use crate::models::ContentProcessor;
+use crate::filters::CompiledFilter;
+use crate::models::{ArticleOrder, ContentProcessor, FeedAuth, FeedFilter};
 use crate::util::content_extractors;
+use crate::util::http::{self, RequestKind};
 
 #[derive(Debug, Clone)]
 pub struct Article {
@@ -16,73 +19,282 @@ pub struct Article {
     pub content: String,
     pub pub_date: DateTime<Utc>,
     pub source: String,
+    pub category: Option<String>,
+    /// The source feed's position in the user's feed order.
+    pub priority: i64,
+    pub feed_id: Option<i64>,
+    pub item_key: Option<String>,
+    /// Items from other feeds that pointed at the same page and were dropped in favour of this one.
+    pub duplicate_keys: Vec<(i64, String)>,
+    /// The feed entry's authors, or the page's byline when the feed names none.
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    pub excerpt: Option<String>,
+    pub lead_image: Option<String>,
+    /// The page's `rel=canonical` URL, used only to spot duplicates; `link` stays the feed's own link.
+    pub canonical_url: Option<String>,
+    /// Extra attempts spent fetching the article page, added to the feed's health record.
+    pub retries: u32,
 }
 
 pub struct FeedWrapper {
     pub feed: Feed,
     pub limit: usize,
     pub processor: Option<ContentProcessor>,
+    pub feed_id: Option<i64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Overrides the global cutoff passed to `filter_items` for this feed.
+    pub since: Option<DateTime<Utc>>,
+    /// Keys of items already delivered in an earlier digest.
+    pub seen: HashSet<String>,
+    pub fetch_since_hours: Option<i32>,
+    pub max_items: Option<usize>,
+    pub filter: Option<FeedFilter>,
+    pub feed_url: String,
+    pub auth: Option<FeedAuth>,
+    pub proxy_url: Option<String>,
+    pub category: Option<String>,
+    pub priority: i64,
+}
+
+/// Outcome of fetching a single feed, recorded as the feed's health.
+pub struct FetchStatus {
+    pub feed_id: Option<i64>,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    pub retries: u32,
+}
+
+/// Stable key used to remember that an entry has been delivered.
+pub fn item_key(entry: &Entry) -> String {
+    if !entry.id.is_empty() {
+        return entry.id.clone();
+    }
+    entry
+        .links
+        .first()
+        .map(|l| l.href.clone())
+        .unwrap_or_default()
+}
+
+fn entry_authors(entry: &Entry) -> Option<String> {
+    let names: Vec<&str> = entry
+        .authors
+        .iter()
+        .map(|p| p.name.trim())
+        .filter(|n| !n.is_empty())
+        .collect();
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+/// The entry's categories, preferring the human-readable label over the term.
+fn entry_tags(entry: &Entry) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for category in &entry.categories {
+        let tag = category.label.as_deref().unwrap_or(&category.term).trim();
+        if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
 }
 
 pub async fn fetch_feeds(
     db_feeds: &Vec<crate::models::Feed>,
-) -> (Vec<FeedWrapper>, Vec<(String, String)>) {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(Duration::from_secs( 45))
-        .cookie_store(true)
-        .build()
-        .unwrap_or_else(|_| Client::new());
+    max_concurrent: usize,
+    max_per_host: usize,
+) -> (Vec<FeedWrapper>, Vec<(String, String)>, Vec<FetchStatus>) {
+    let global_semaphore = Arc::new(Semaphore::new(max_concurrent.max(1)));
+    let mut host_semaphores: HashMap<String, Arc<Semaphore>> = HashMap::new();
+    let mut join_set = tokio::task::JoinSet::new();
+
+    for (index, db_feed) in db_feeds.iter().enumerate() {
+        let host = content_extractors::extract_domain(&db_feed.url).unwrap_or_default();
+        let host_semaphore = host_semaphores
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(max_per_host.max(1))))
+            .clone();
+        let global_semaphore = global_semaphore.clone();
+        let db_feed = db_feed.clone();
+
+        join_set.spawn(async move {
+            // Take the host slot first so feeds queued behind a busy host do not hold global slots.
+            let _host_permit = host_semaphore.acquire_owned().await.unwrap();
+            let _permit = global_semaphore.acquire_owned().await.unwrap();
+            let mut http_status = None;
+            let mut retries = 0;
+            let result = fetch_feed(&db_feed, &mut http_status, &mut retries).await;
+            let status = FetchStatus {
+                feed_id: db_feed.id,
+                http_status,
+                error: result.as_ref().err().cloned(),
+                retries,
+            };
+            (index, db_feed.url, result, status)
+        });
+    }
+
+    let mut results = Vec::with_capacity(db_feeds.len());
+    while let Some(res) = join_set.join_next().await {
+        match res {
+            Ok(result) => results.push(result),
+            Err(e) => error!("Task join error: {}", e),
+        }
+    }
+    results.sort_by_key(|(index, _, _, _)| *index);
 
     let mut feeds = Vec::new();
     let mut errors = Vec::new();
-    let feed_info: Vec<(String, usize, Option<ContentProcessor>)> = db_feeds
-        .into_iter()
-        .map(|f| (f.url.clone(), f.concurrency_limit, Some(f.feed_processor.clone())))
-        .collect();
-    for (string_url, limit, processor) in feed_info {
-        let url: &str = &string_url;
-        match client.get(url).send().await {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    let msg = format!("Failed to fetch feed: HTTP {}", resp.status());
-                    warn!("{} - {}", msg, url);
-                    errors.push((format!("{}", url), msg));
-                    continue;
-                }
+    let mut statuses = Vec::new();
+    for (_, url, result, status) in results {
+        match result {
+            Ok(Some(feed)) => feeds.push(feed),
+            Ok(None) => {}
+            Err(msg) => errors.push((url, msg)),
+        }
+        statuses.push(status);
+    }
 
-                match resp.bytes().await {
-                    Ok(content) => match parser::parse(&content[..]) {
-                        Ok(feed) => {
-                            info!("Successfully fetched and parsed feed: {}", url);
-                            feeds.push(FeedWrapper {
-                                feed,
-                                limit,
-                                processor,
-                            });
-                        }
-                        Err(e) => {
-                            let msg = format!("Failed to parse RSS feed: {}", e);
-                            warn!("{} - {}", msg, url);
-                            errors.push((format!("{}", url), msg));
-                        }
-                    },
+    (feeds, errors, statuses)
+}
+
+/// Returns `Ok(None)` when the server answered `304 Not Modified`.
+/// `http_status` is set as soon as a response is received, `retries` once the request is done.
+async fn fetch_feed(
+    db_feed: &crate::models::Feed,
+    http_status: &mut Option<u16>,
+    retries: &mut u32,
+) -> Result<Option<FeedWrapper>, String> {
+    let url: &str = &db_feed.url;
+    let client = http::client_with_auth(url, db_feed.proxy_url.as_deref(), db_feed.auth.as_ref()).map_err(|e| e.to_string())?;
+    let mut request = client.get(url).timeout(http::timeout_for(RequestKind::Feed));
+    if let Some(auth) = &db_feed.auth {
+        request = crate::util::http::apply_feed_auth(request, auth);
+    }
+    if let Some(etag) = &db_feed.etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &db_feed.last_modified {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+    let (result, request_retries) = http::send_with_retry(request).await;
+    *retries = request_retries;
+    match result {
+        Ok(resp) => {
+            *http_status = Some(resp.status().as_u16());
+            if resp.status() == StatusCode::NOT_MODIFIED {
+                info!("Feed not modified since last fetch: {}", url);
+                return Ok(None);
+            }
+            if !resp.status().is_success() {
+                let msg = format!("Failed to fetch feed: HTTP {}", resp.status());
+                warn!("{} - {}", msg, url);
+                return Err(msg);
+            }
+
+            let etag = header_value(&resp, header::ETAG);
+            let last_modified = header_value(&resp, header::LAST_MODIFIED);
+            match resp.bytes().await {
+                Ok(content) => match parser::parse(&content[..]) {
+                    Ok(feed) => {
+                        info!("Successfully fetched and parsed feed: {}", url);
+                        Ok(Some(FeedWrapper {
+                            feed,
+                            limit: db_feed.concurrency_limit,
+                            processor: Some(db_feed.feed_processor.clone()),
+                            feed_id: db_feed.id,
+                            etag,
+                            last_modified,
+                            since: None,
+                            seen: HashSet::new(),
+                            fetch_since_hours: db_feed.fetch_since_hours,
+                            max_items: db_feed.max_items,
+                            filter: db_feed.filter.clone(),
+                            feed_url: db_feed.url.clone(),
+                            auth: db_feed.auth.clone(),
+                            proxy_url: db_feed.proxy_url.clone(),
+                            category: db_feed.category.clone(),
+                            priority: db_feed.priority,
+                        }))
+                    }
                     Err(e) => {
-                        let msg = format!("Failed to read response body: {}", e);
+                        let msg = format!("Failed to parse RSS feed: {}", e);
                         warn!("{} - {}", msg, url);
-                        errors.push((format!("{}", url), msg));
+                        Err(msg)
                     }
+                },
+                Err(e) => {
+                    let msg = format!("Failed to read response body: {}", e);
+                    warn!("{} - {}", msg, url);
+                    Err(msg)
                 }
             }
-            Err(e) => {
-                let msg = format!("Failed to fetch URL: {}", e);
-                warn!("{} - {}", msg, url);
-                errors.push((format!("{}", url), msg));
+        }
+        Err(e) => {
+            let msg = format!("Failed to fetch URL: {}", e);
+            warn!("{} - {}", msg, url);
+            Err(msg)
+        }
+    }
+}
+
+/// Collapses articles whose pages resolved to the same canonical URL, keeping the first one.
+pub fn dedupe_articles(articles: Vec<Article>) -> Vec<Article> {
+    let mut kept: Vec<Article> = Vec::with_capacity(articles.len());
+    let mut by_link: HashMap<String, usize> = HashMap::new();
+    for article in articles {
+        let link = article.canonical_url.as_deref().unwrap_or(&article.link);
+        if link.is_empty() {
+            kept.push(article);
+            continue;
+        }
+        let link_key = crate::util::canonicalize_url(link);
+        match by_link.get(&link_key) {
+            Some(&i) => {
+                info!("Dropping duplicate of '{}': {}", kept[i].title, article.link);
+                let own = article.feed_id.zip(article.item_key);
+                kept[i].duplicate_keys.extend(own.into_iter().chain(article.duplicate_keys));
+            }
+            None => {
+                by_link.insert(link_key, kept.len());
+                kept.push(article);
             }
         }
     }
+    kept
+}
 
-    (feeds, errors)
+fn header_value(resp: &reqwest::Response, name: header::HeaderName) -> Option<String> {
+    resp.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// One "System Errors" article per feed that failed to load, given as `(url, error)` pairs.
+pub fn error_articles(errors: Vec<(String, String)>) -> Vec<Article> {
+    errors
+        .into_iter()
+        .map(|(url, error_msg)| Article {
+            title: format!("Error loading feed: {}", url),
+            link: url.clone(),
+            content: format!("<h1>Error loading feed</h1><p><strong>URL:</strong> {}</p><p><strong>Error:</strong> {}</p>", url, error_msg),
+            pub_date: Utc::now(),
+            source: "System Errors".to_string(),
+            category: None,
+            priority: i64::MAX,
+            feed_id: None,
+            item_key: None,
+            duplicate_keys: Vec::new(),
+            author: None,
+            tags: Vec::new(),
+            excerpt: None,
+            lead_image: None,
+            canonical_url: None,
+            retries: 0,
+        })
+        .collect()
 }
 
 pub async fn filter_items(
@@ -91,27 +303,34 @@ pub async fn filter_items(
     since: DateTime<Utc>,
 ) -> Vec<Article> {
     let mut articles = Vec::new();
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .cookie_store(true)
-        .build()
-        .unwrap_or_else(|_| Client::new());
     let mut join_set = tokio::task::JoinSet::new();
+    let mut spawned = 0usize;
+    // Normalised link -> keys of later items carrying the same link, which are not fetched again.
+    let mut claimed_links: HashMap<String, Vec<(i64, String)>> = HashMap::new();
 
-    for (url, error_msg) in errors {
-        articles.push(Article {
-            title: format!("Error loading feed: {}", url),
-            link: url.clone(),
-            content: format!("<h1>Error loading feed</h1><p><strong>URL:</strong> {}</p><p><strong>Error:</strong> {}</p>", url, error_msg),
-            pub_date: Utc::now(),
-            source: "System Errors".to_string(),
-        });
-    }
+    articles.extend(error_articles(errors));
 
     for feed_wrapper in feeds {
         let feed = feed_wrapper.feed;
         let limit = feed_wrapper.limit;
         let processor = feed_wrapper.processor;
+        let feed_id = feed_wrapper.feed_id;
+        let feed_since = feed_wrapper.since.unwrap_or(since);
+        let seen = feed_wrapper.seen;
+        let max_items = feed_wrapper.max_items.filter(|n| *n > 0);
+        let entry_filter = feed_wrapper.filter.as_ref().and_then(|f| match CompiledFilter::new(f) {
+            Ok(compiled) => Some(compiled),
+            Err(e) => {
+                warn!("Ignoring invalid filter for feed {:?}: {}", feed_wrapper.feed_id, e);
+                None
+            }
+        });
+        // Credentials only go to the feed's own host, never to sites the feed links out to.
+        let feed_host = content_extractors::extract_domain(&feed_wrapper.feed_url);
+        let feed_auth = feed_wrapper.auth;
+        let feed_proxy = feed_wrapper.proxy_url;
+        let feed_category = feed_wrapper.category;
+        let feed_priority = feed_wrapper.priority;
         let semaphore = if limit > 0 {
             Some(Arc::new(Semaphore::new(limit)))
         } else {
@@ -121,77 +340,261 @@ pub async fn filter_items(
             .title
             .map(|t| t.content)
             .unwrap_or("Unknown Source".to_string());
+        let mut candidates = Vec::new();
         for entry in feed.entries {
-            if let Some(pub_date) = entry.published.or(entry.updated) {
-                if pub_date >= since {
-                    let title = entry
-                        .title
-                        .as_ref()
-                        .map(|t| t.content.clone())
-                        .unwrap_or("No Title".to_string());
-
-                    let link = entry
-                        .links
-                        .iter()
-                        .find(|l| l.rel.as_deref() == Some("alternate") || l.rel.is_none())
-                        .map(|l| l.href.clone())
-                        .unwrap_or_default();
-
-                    let client = client.clone();
-                    let source_title = source_title.clone();
-                    let entry = entry.clone();
-                    let semaphore = semaphore.clone();
-                    let processor = processor.clone();
-
-                    join_set.spawn(async move {
-                        let _permit = if let Some(sem) = semaphore {
-                            Some(sem.acquire_owned().await.unwrap())
-                        } else {
-                            None
-                        };
-                        info!("Processing article: {}", title);
-
-
-                        let content = if !link.is_empty() {
-                            match content_extractors::fetch_full_content_with_processor(&client, &link, processor.as_ref()).await {
-                                //should use extracted title ?
-                                Ok((_title, c)) => c,
-                                Err(e) => {
-                                    error!("Error fetching full content for '{}': {}", link, e);
-                                    let error_html = format!("<p style=\"color:red\"><strong>Error fetching full content:</strong> {}</p><hr/>", e);
-                                    let fallback = entry.content.map(|c| c.body.unwrap_or_default())
-                                        .or(entry.summary.map(|s| s.content))
-                                        .unwrap_or_default();
-                                    format!("{}{}", error_html, fallback)
-                                }
-                            }
-                        } else {
-                            entry.content.map(|c| c.body.unwrap_or_default())
-                                .or(entry.summary.map(|s| s.content))
-                                .unwrap_or_default()
-                        };
-
-                        Article {
-                            title,
-                            link,
-                            content,
-                            pub_date,
-                            source: source_title,
-                        }
-                    });
+            let key = item_key(&entry);
+            if seen.contains(&key) {
+                continue;
+            }
+            if let Some(entry_filter) = &entry_filter
+                && !entry_filter.allows(&entry)
+            {
+                continue;
+            }
+            if let Some(pub_date) = entry.published.or(entry.updated)
+                && pub_date >= feed_since
+            {
+                candidates.push((entry, pub_date, key));
+            }
+        }
+        if let Some(max_items) = max_items {
+            candidates.sort_by_key(|(_, pub_date, _)| std::cmp::Reverse(*pub_date));
+            candidates.truncate(max_items);
+        }
+
+        for (entry, pub_date, key) in candidates {
+            let title = entry
+                .title
+                .as_ref()
+                .map(|t| t.content.trim().to_string())
+                .filter(|t| !t.is_empty());
+            let author = entry_authors(&entry);
+            let tags = entry_tags(&entry);
+
+            let link = entry
+                .links
+                .iter()
+                .find(|l| l.rel.as_deref() == Some("alternate") || l.rel.is_none())
+                .map(|l| l.href.trim().to_string())
+                .unwrap_or_default();
+            // The normalised form is only used to spot duplicates; the article keeps the feed's link.
+            let link_key = crate::util::canonicalize_url(&link);
+
+            if !link.is_empty() {
+                if let Some(duplicates) = claimed_links.get_mut(&link_key) {
+                    info!("Skipping duplicate article: {}", link);
+                    if let Some(feed_id) = feed_id {
+                        duplicates.push((feed_id, key));
+                    }
+                    continue;
                 }
+                claimed_links.insert(link_key.clone(), Vec::new());
             }
+
+            let feed_proxy = feed_proxy.clone();
+            let source_title = source_title.clone();
+            let category = feed_category.clone();
+            let semaphore = semaphore.clone();
+            let processor = processor.clone();
+            let auth = feed_auth
+                .clone()
+                .filter(|_| feed_host.is_some() && content_extractors::extract_domain(&link) == feed_host);
+            let index = spawned;
+            spawned += 1;
+
+            join_set.spawn(async move {
+                let _permit = if let Some(sem) = semaphore {
+                    Some(sem.acquire_owned().await.unwrap())
+                } else {
+                    None
+                };
+                info!("Processing article: {}", title.as_deref().unwrap_or(&link));
+
+
+                let feed_content = entry.content.map(|c| c.body.unwrap_or_default())
+                    .or(entry.summary.map(|s| s.content))
+                    .unwrap_or_default();
+
+                let mut canonical_url = None;
+                let mut title = title;
+                let mut author = author;
+                let mut excerpt = None;
+                let mut lead_image = None;
+                let mut retries = 0;
+                let content = if !link.is_empty() {
+                    let fetched = match http::client_with_auth(&link, feed_proxy.as_deref(), auth.as_ref()) {
+                        Ok(client) => content_extractors::fetch_full_content_with_processor(&client, &link, processor.as_ref(), auth.as_ref(), Some(&feed_content)).await,
+                        Err(e) => Err(e),
+                    };
+                    match fetched {
+                        Ok(fetched) => {
+                            if fetched.retries > 0 {
+                                warn!("Feed {:?}: article {} needed {} retries", feed_id, link, fetched.retries);
+                            }
+                            canonical_url = fetched.canonical_url;
+                            title = title.or(Some(fetched.title).filter(|t| !t.trim().is_empty()));
+                            author = author.or(fetched.byline);
+                            excerpt = fetched.excerpt;
+                            lead_image = fetched.lead_image;
+                            retries = fetched.retries;
+                            fetched.content
+                        }
+                        Err(e) => {
+                            error!("Error fetching full content for '{}': {}", link, e);
+                            let error_html = format!("<p style=\"color:red\"><strong>Error fetching full content:</strong> {}</p><hr/>", e);
+                            format!("{}{}", error_html, feed_content)
+                        }
+                    }
+                } else {
+                    feed_content
+                };
+
+                let article = Article {
+                    title: title.unwrap_or("No Title".to_string()),
+                    link,
+                    content,
+                    pub_date,
+                    source: source_title,
+                    category,
+                    priority: feed_priority,
+                    feed_id,
+                    item_key: Some(key),
+                    duplicate_keys: Vec::new(),
+                    author,
+                    tags,
+                    excerpt,
+                    lead_image,
+                    canonical_url,
+                    retries,
+                };
+                (index, link_key, article)
+            });
         }
     }
 
+    let mut fetched = Vec::new();
     while let Some(res) = join_set.join_next().await {
         match res {
-            Ok(article) => articles.push(article),
+            Ok(result) => fetched.push(result),
             Err(e) => error!("Task join error: {}", e),
         }
     }
+    fetched.sort_by_key(|(index, _, _)| *index);
+
+    let mut fetched_articles = Vec::with_capacity(fetched.len());
+    for (_, link_key, mut article) in fetched {
+        if let Some(duplicates) = claimed_links.remove(&link_key) {
+            article.duplicate_keys.extend(duplicates);
+        }
+        fetched_articles.push(article);
+    }
+    articles.extend(dedupe_articles(fetched_articles));
 
     articles.sort_by(|a, b| b.pub_date.cmp(&a.pub_date));
 
     articles
 }
+
+/// Puts newest-first articles into the order they are read in the digest.
+pub fn order_articles(mut articles: Vec<Article>, order: ArticleOrder) -> Vec<Article> {
+    let by_feed_order = |a: &Article, b: &Article| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| b.pub_date.cmp(&a.pub_date))
+    };
+    match order {
+        ArticleOrder::NewestFirst => articles.sort_by_key(|a| Reverse(a.pub_date)),
+        ArticleOrder::OldestFirst => articles.sort_by_key(|a| a.pub_date),
+        ArticleOrder::FeedPriority => articles.sort_by(by_feed_order),
+        ArticleOrder::RoundRobin => {
+            articles.sort_by(by_feed_order);
+            let mut queues: Vec<VecDeque<Article>> = Vec::new();
+            for article in articles {
+                match queues.last_mut() {
+                    Some(queue) if queue[0].priority == article.priority && queue[0].source == article.source => {
+                        queue.push_back(article)
+                    }
+                    _ => queues.push(VecDeque::from([article])),
+                }
+            }
+            let mut interleaved = Vec::new();
+            while queues.iter().any(|q| !q.is_empty()) {
+                interleaved.extend(queues.iter_mut().filter_map(VecDeque::pop_front));
+            }
+            return interleaved;
+        }
+    }
+    articles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(source: &str, priority: i64, hours_ago: i64) -> Article {
+        Article {
+            title: format!("{} {}", source, hours_ago),
+            link: String::new(),
+            content: String::new(),
+            pub_date: Utc::now() - chrono::Duration::hours(hours_ago),
+            source: source.to_string(),
+            category: None,
+            priority,
+            feed_id: None,
+            item_key: None,
+            duplicate_keys: Vec::new(),
+            author: None,
+            tags: Vec::new(),
+            excerpt: None,
+            lead_image: None,
+            canonical_url: None,
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_order_articles_round_robin() {
+        let articles = vec![
+            article("B", 1, 1),
+            article("A", 0, 5),
+            article("A", 0, 2),
+            article("B", 1, 3),
+            article("A", 0, 1),
+        ];
+
+        let titles: Vec<String> = order_articles(articles, ArticleOrder::RoundRobin)
+            .into_iter()
+            .map(|a| a.title)
+            .collect();
+        assert_eq!(titles, vec!["A 1", "B 1", "A 2", "B 3", "A 5"]);
+    }
+
+    #[test]
+    fn test_dedupe_keeps_original_link() {
+        let mut first = article("A", 0, 1);
+        first.link = "https://example.com/post?utm_source=rss".to_string();
+        let mut second = article("B", 1, 2);
+        second.link = "https://Example.com/post#comments".to_string();
+        second.feed_id = Some(2);
+        second.item_key = Some("b".to_string());
+
+        let kept = dedupe_articles(vec![first, second]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].link, "https://example.com/post?utm_source=rss");
+        assert_eq!(kept[0].duplicate_keys, vec![(2, "b".to_string())]);
+    }
+
+    #[test]
+    fn test_dedupe_by_canonical_url_keeps_feed_link() {
+        let mut first = article("A", 0, 1);
+        first.link = "https://feeds.example.com/~r/post".to_string();
+        first.canonical_url = Some("https://example.com/post".to_string());
+        let mut second = article("B", 1, 2);
+        second.link = "https://example.com/post?utm_medium=feed".to_string();
+
+        let kept = dedupe_articles(vec![first, second]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].link, "https://feeds.example.com/~r/post");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::{FeedFilter, FilterField, FilterRule};
+use feed_rs::model::Entry;
+use regex::{Regex, RegexBuilder};
+
+enum Matcher {
+    Keyword(String),
+    Pattern(Regex),
+}
+
+struct CompiledRule {
+    field: FilterField,
+    matcher: Matcher,
+}
+
+impl CompiledRule {
+    fn new(rule: &FilterRule) -> Result<Self, regex::Error> {
+        let matcher = if rule.regex {
+            Matcher::Pattern(RegexBuilder::new(&rule.pattern).case_insensitive(true).build()?)
+        } else {
+            Matcher::Keyword(rule.pattern.to_lowercase())
+        };
+        Ok(Self {
+            field: rule.field,
+            matcher,
+        })
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        field_values(entry, self.field)
+            .iter()
+            .any(|value| match &self.matcher {
+                Matcher::Keyword(keyword) => value.to_lowercase().contains(keyword),
+                Matcher::Pattern(regex) => regex.is_match(value),
+            })
+    }
+}
+
+/// A feed's include/exclude rules, ready to be applied to entries.
+pub struct CompiledFilter {
+    include: Vec<CompiledRule>,
+    exclude: Vec<CompiledRule>,
+}
+
+impl CompiledFilter {
+    pub fn new(filter: &FeedFilter) -> Result<Self, regex::Error> {
+        Ok(Self {
+            include: filter.include.iter().map(CompiledRule::new).collect::<Result<_, _>>()?,
+            exclude: filter.exclude.iter().map(CompiledRule::new).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// An entry is kept when it matches any include rule (or there are none) and no exclude rule.
+    pub fn allows(&self, entry: &Entry) -> bool {
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.matches(entry)) {
+            return false;
+        }
+        !self.exclude.iter().any(|r| r.matches(entry))
+    }
+}
+
+fn field_values(entry: &Entry, field: FilterField) -> Vec<&str> {
+    let mut values = Vec::new();
+    if matches!(field, FilterField::Title | FilterField::Any)
+        && let Some(title) = &entry.title
+    {
+        values.push(title.content.as_str());
+    }
+    if matches!(field, FilterField::Author | FilterField::Any) {
+        values.extend(entry.authors.iter().map(|a| a.name.as_str()));
+    }
+    if matches!(field, FilterField::Category | FilterField::Any) {
+        for category in &entry.categories {
+            values.push(category.term.as_str());
+            if let Some(label) = &category.label {
+                values.push(label.as_str());
+            }
+        }
+    }
+    if matches!(field, FilterField::Content | FilterField::Any) {
+        if let Some(body) = entry.content.as_ref().and_then(|c| c.body.as_deref()) {
+            values.push(body);
+        }
+        if let Some(summary) = &entry.summary {
+            values.push(summary.content.as_str());
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, category: &str) -> Entry {
+        let xml = format!(
+            r#"<?xml version="1.0"?><rss version="2.0"><channel><title>t</title>
+            <item><title>{}</title><category>{}</category><description>body</description></item>
+            </channel></rss>"#,
+            title, category
+        );
+        feed_rs::parser::parse(xml.as_bytes()).unwrap().entries.remove(0)
+    }
+
+    fn rule(field: FilterField, pattern: &str, regex: bool) -> FilterRule {
+        FilterRule {
+            field,
+            pattern: pattern.to_string(),
+            regex,
+        }
+    }
+
+    #[test]
+    fn test_filter_include_and_exclude() {
+        let filter = CompiledFilter::new(&FeedFilter {
+            include: vec![rule(FilterField::Category, "rust", false)],
+            exclude: vec![rule(FilterField::Title, r"^\[sponsored\]", true)],
+        })
+        .unwrap();
+
+        assert!(filter.allows(&entry("Async in practice", "Rust")));
+        assert!(!filter.allows(&entry("Async in practice", "Go")));
+        assert!(!filter.allows(&entry("[Sponsored] Faster builds", "rust")));
+    }
+
+    #[test]
+    fn test_filter_rejects_invalid_regex() {
+        let filter = FeedFilter {
+            include: vec![],
+            exclude: vec![rule(FilterField::Any, "(unclosed", true)],
+        };
+        assert!(CompiledFilter::new(&filter).is_err());
+    }
+}
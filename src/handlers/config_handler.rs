@@ -2,6 +2,7 @@ use std::sync::Arc;
 use axum::{extract::{State, Json}, http::StatusCode};
 use crate::models::{AppState, GeneralConfig};
 use crate::db;
+use crate::util::http;
 
 pub async fn get_general_config(
     State(state): State<Arc<AppState>>,
@@ -19,10 +20,28 @@ pub async fn get_general_config(
     Ok(Json(config))
 }
 
+pub async fn purge_cache(
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+
+    db::purge_content_cache(&db, None)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn update_general_config(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<GeneralConfig>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    http::validate_proxy_url(payload.proxy_url.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     let db = state.db.lock().map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -32,6 +51,8 @@ pub async fn update_general_config(
 
     db::update_general_config(&db, &payload)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    http::reload_http_settings(&db)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(StatusCode::OK)
 }
@@ -0,0 +1,99 @@
+use std::sync::{Arc, MutexGuard};
+use axum::{
+    extract::{Multipart, Path, State, Json},
+    http::StatusCode,
+};
+use rusqlite::Connection;
+use tracing::info;
+use crate::models::{AppState, DomainCookies};
+use crate::db;
+use crate::util::cookies;
+
+pub async fn list_domain_cookies(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DomainCookies>>, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+
+    let jars = db::get_domain_cookies(&db)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(jars))
+}
+
+/// Accepts a multipart form with a `domain` field and a `file` field holding a Netscape `cookies.txt` export.
+pub async fn upload_domain_cookies(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let mut domain = None;
+    let mut cookies_txt = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Failed to read multipart field: {}", e),
+        )
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+        let text = field.text().await.map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read field: {}", e),
+            )
+        })?;
+        match name.as_str() {
+            "domain" => domain = Some(text.trim().trim_start_matches('.').to_lowercase()),
+            "file" => cookies_txt = Some(text),
+            _ => {}
+        }
+    }
+
+    let domain = domain
+        .filter(|d| !d.is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "Domain is required".to_string()))?;
+    let cookies_txt = cookies_txt.ok_or((StatusCode::BAD_REQUEST, "No cookies file found".to_string()))?;
+    let count = cookies::parse_netscape_cookies(&cookies_txt).len();
+    if count == 0 {
+        return Err((StatusCode::BAD_REQUEST, "No cookies found in file".to_string()));
+    }
+
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    db::save_domain_cookies(&db, &domain, &cookies_txt)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    refresh_cookie_map(&db);
+    info!("Stored {} cookies for {}", count, domain);
+    Ok(StatusCode::CREATED)
+}
+
+pub async fn delete_domain_cookies(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+
+    db::delete_domain_cookies(&db, id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    refresh_cookie_map(&db);
+    Ok(StatusCode::OK)
+}
+
+fn refresh_cookie_map(db: &MutexGuard<Connection>) {
+    if let Ok(jars) = db::get_domain_cookie_jars(db) {
+        cookies::refresh_domain_cookies(jars);
+    }
+}
@@ -6,7 +6,8 @@ use axum::{
 use rusqlite::Connection;
 use crate::models::{AddDomainOverrideRequest, AppState, DomainOverride};
 use crate::db;
-use crate::util::content_extractors;
+use crate::util::{content_extractors, http};
+use tracing::warn;
 
 pub async fn list_domain_overrides(
     State(state): State<Arc<AppState>>,
@@ -28,6 +29,10 @@ pub async fn add_domain_override(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<AddDomainOverrideRequest>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    http::validate_proxy_url(payload.proxy_url.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    content_extractors::validate_processor(payload.processor, &payload.fallbacks, payload.custom_config.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
     let db = state.db.lock().map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -35,7 +40,14 @@ pub async fn add_domain_override(
         )
     })?;
 
-    db::add_domain_override(&db, &payload.domain, payload.processor, payload.custom_config.as_deref())
+    db::add_domain_override(
+        &db,
+        &payload.domain,
+        payload.processor,
+        payload.custom_config.as_deref(),
+        payload.proxy_url.as_deref().map(str::trim).filter(|p| !p.is_empty()),
+        &payload.fallbacks,
+    )
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     refresh_domain_processor_map(&db);
     Ok(StatusCode::CREATED)
@@ -65,8 +77,12 @@ fn refresh_domain_processor_map(db: &MutexGuard<Connection>) {
                 id: o.id,
                 processor: o.processor,
                 custom_config: o.custom_config,
+                fallbacks: o.fallbacks,
             }))
             .collect();
         content_extractors::refresh_domain_overrides(override_list);
     }
+    if let Err(e) = http::reload_http_settings(db) {
+        warn!("Failed to reload HTTP settings: {}", e);
+    }
 }
@@ -6,7 +6,7 @@ use axum::extract::State;
 use axum::response::{IntoResponse, Response};
 use tracing::info;
 use crate::models::{AppState, GenerateRequest};
-use crate::{db, email, processor, util};
+use crate::{db, email, processor, subscriptions, util};
 
 const RSS_DIGEST: &'static str = "rss_digest_";
 const READ_IT_LATER: &'static str = "read_it_later_";
@@ -54,7 +54,19 @@ pub async fn generate_epub_adhoc(
         })?;
         let stored_feeds =
             db::get_feeds(&db).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-        stored_feeds
+        if payload.categories.is_empty() {
+            stored_feeds
+        } else {
+            stored_feeds
+                .into_iter()
+                .filter(|f| {
+                    payload
+                        .categories
+                        .iter()
+                        .any(|c| subscriptions::category_matches(f.category.as_deref(), c))
+                })
+                .collect()
+        }
     } else {
         payload.feeds
     };
@@ -0,0 +1,69 @@
+use std::time::Instant;
+use axum::{http::StatusCode, Json};
+use crate::models::{ContentProcessor, ExtractPreviewRequest, ExtractPreviewResponse};
+use crate::util::{content_extractors, http};
+
+/// Runs a processor against one page without saving anything, for tuning Custom configs.
+/// Domain overrides are ignored so the submitted processor is the one being tested.
+pub async fn preview_extraction(
+    Json(payload): Json<ExtractPreviewRequest>,
+) -> Result<Json<ExtractPreviewResponse>, (StatusCode, String)> {
+    content_extractors::validate_processor(payload.processor, &payload.fallbacks, payload.custom_config.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let url = payload.url.as_deref().map(str::trim).filter(|u| !u.is_empty());
+    if let Some(url) = url
+        && !url::Url::parse(url).is_ok_and(|u| u.scheme() == "http" || u.scheme() == "https")
+    {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid URL '{}'", url)));
+    }
+
+    let (html, fetch_ms) = match payload.html.filter(|h| !h.trim().is_empty()) {
+        Some(html) => (html, None),
+        None => {
+            let url = url.ok_or((StatusCode::BAD_REQUEST, "Provide a url or html".to_string()))?;
+            let started = Instant::now();
+            let client = http::client_for(url, None).map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+            let page = content_extractors::fetch_html(&client, url, None, true)
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to fetch {}: {}", url, e)))?;
+            (page.html, Some(started.elapsed().as_millis() as u64))
+        }
+    };
+    // Readability needs a base URL to resolve relative links in pasted HTML.
+    let page_url = url.unwrap_or("http://localhost/");
+
+    let processor = ContentProcessor {
+        id: None,
+        processor: payload.processor,
+        custom_config: payload.custom_config,
+        fallbacks: payload.fallbacks,
+    };
+    let started = Instant::now();
+    let result = content_extractors::run_chain(
+        Some(&html),
+        page_url,
+        &processor.chain(),
+        processor.custom_config.as_deref(),
+        payload.feed_content.as_deref(),
+    )
+    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    let extract_ms = started.elapsed().as_millis() as u64;
+
+    let extracted = result.extracted;
+    Ok(Json(ExtractPreviewResponse {
+        warnings: content_extractors::extraction_warnings(&html, &processor, &extracted.title, &extracted.content),
+        next_page: result
+            .extractor
+            .and_then(|e| content_extractors::find_next_page(&html, page_url, e.next_page_selector())),
+        step: result.step,
+        rejected: result.rejected,
+        title: extracted.title,
+        content: extracted.content,
+        byline: extracted.byline,
+        excerpt: extracted.excerpt,
+        lead_image: extracted.lead_image,
+        fetch_ms,
+        extract_ms,
+    }))
+}
@@ -1,10 +1,13 @@
 use std::sync::Arc;
 use axum::extract::{Multipart, Path, State};
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
 use axum::Json;
 use serde::Deserialize;
-use crate::db;
-use crate::models::{AddFeedRequest, AppState, Feed, ContentProcessor, ProcessorType};
+use crate::{db, discovery, subscriptions, util};
+use crate::filters::CompiledFilter;
+use crate::util::content_extractors;
+use crate::models::{AddFeedRequest, AppState, CategorySummary, ContentProcessor, DiscoverFeedRequest, DiscoverFeedResponse, Feed, FeedAuth, FeedFilter, FeedOrderRequest, ProcessorType, RenameCategoryRequest, UpdateFeedRequest};
 
 pub async fn list_feeds(
     State(state): State<Arc<AppState>>,
@@ -15,8 +18,12 @@ pub async fn list_feeds(
             "DB lock failed".to_string(),
         )
     })?;
-    let feeds =
+    let mut feeds =
         db::get_feeds(&db).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Like feed secrets, proxy credentials are write-only; the stored URL keeps them.
+    for feed in &mut feeds {
+        feed.proxy_url = feed.proxy_url.as_deref().map(util::http::redact_proxy_url);
+    }
     Ok(Json(feeds))
 }
 
@@ -24,6 +31,12 @@ pub async fn add_feed(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<AddFeedRequest>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    validate_feed_limits(payload.fetch_since_hours, payload.max_items)?;
+    if let Some(processor) = payload.processor {
+        content_extractors::validate_processor(processor, &payload.fallbacks, payload.custom_config.as_deref())
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+
     let db = state.db.lock().map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -36,18 +49,77 @@ pub async fn add_feed(
         &payload.url,
         payload.name.as_deref(),
         payload.concurrency_limit,
+        payload.fetch_since_hours,
+        payload.max_items,
+        subscriptions::normalize_category(payload.category.as_deref()).as_deref(),
     )
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     
-    if let Some(processor) = payload.processor {
-        if processor != ProcessorType::Default {
-        let _ = db::save_feed_processor(&db,feed_id,processor,payload.custom_config.as_deref(),);
-        }
+    if let Some(processor) = payload.processor
+        && (processor != ProcessorType::Default || !payload.fallbacks.is_empty())
+    {
+        db::save_feed_processor(&db, feed_id, processor, payload.custom_config.as_deref(), &payload.fallbacks)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     }
     
     Ok(StatusCode::CREATED)
 }
 
+/// A zero or negative window or item limit would leave the feed out of every digest; clearing the
+/// override (null) falls back to the global settings instead.
+fn validate_feed_limits(fetch_since_hours: Option<i32>, max_items: Option<usize>) -> Result<(), (StatusCode, String)> {
+    if fetch_since_hours.is_some_and(|hours| hours <= 0) {
+        return Err((StatusCode::BAD_REQUEST, "fetch_since_hours must be greater than 0".to_string()));
+    }
+    if max_items == Some(0) {
+        return Err((StatusCode::BAD_REQUEST, "max_items must be greater than 0".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn discover_feed(
+    Json(payload): Json<DiscoverFeedRequest>,
+) -> Result<Json<DiscoverFeedResponse>, (StatusCode, String)> {
+    let is_web_url = url::Url::parse(&payload.url)
+        .map(|u| u.scheme() == "http" || u.scheme() == "https")
+        .unwrap_or(false);
+    if !is_web_url {
+        return Err((StatusCode::BAD_REQUEST, "Invalid URL".to_string()));
+    }
+
+    let result = discovery::discover_feeds(&payload.url)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to fetch page: {}", e)))?;
+    Ok(Json(result))
+}
+
+pub async fn update_feed(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut payload): Json<UpdateFeedRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    validate_feed_limits(payload.fetch_since_hours.flatten(), payload.max_items.flatten())?;
+    if let Some(proxy_url) = &payload.proxy_url {
+        util::http::validate_proxy_url(proxy_url.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    if let Some(category) = &payload.category {
+        payload.category = Some(subscriptions::normalize_category(category.as_deref()));
+    }
+
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let updated = db::update_feed(&db, id, &payload)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if updated == 0 {
+        return Err((StatusCode::NOT_FOUND, "Feed not found".to_string()));
+    }
+    Ok(StatusCode::OK)
+}
+
 pub async fn delete_feed(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
@@ -62,6 +134,63 @@ pub async fn delete_feed(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn reorder_feeds(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<FeedOrderRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let reordered = db::reorder_feeds(&db, &payload.feed_ids)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !reordered {
+        return Err((StatusCode::BAD_REQUEST, "Feed order must list every feed exactly once".to_string()));
+    }
+    Ok(StatusCode::OK)
+}
+
+pub async fn list_categories(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CategorySummary>>, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let categories =
+        db::get_categories(&db).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(categories))
+}
+
+pub async fn rename_category(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RenameCategoryRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (Some(from), Some(to)) = (
+        subscriptions::normalize_category(Some(&payload.from)),
+        subscriptions::normalize_category(Some(&payload.to)),
+    ) else {
+        return Err((StatusCode::BAD_REQUEST, "Category names cannot be empty".to_string()));
+    };
+
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let renamed = db::rename_category(&db, &from, &to)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if renamed == 0 {
+        return Err((StatusCode::NOT_FOUND, "Category not found".to_string()));
+    }
+    Ok(StatusCode::OK)
+}
+
 pub async fn get_feed_processor(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
@@ -81,6 +210,8 @@ pub async fn get_feed_processor(
 pub struct UpdateFeedProcessorRequest {
     pub processor: ProcessorType,
     pub custom_config: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
 }
 
 pub async fn update_feed_processor(
@@ -88,6 +219,9 @@ pub async fn update_feed_processor(
     Path(id): Path<i64>,
     Json(payload): Json<UpdateFeedProcessorRequest>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    content_extractors::validate_processor(payload.processor, &payload.fallbacks, payload.custom_config.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
     let db = state.db.lock().map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -95,17 +229,152 @@ pub async fn update_feed_processor(
         )
     })?;
     
-    if payload.processor == ProcessorType::Default {
+    if payload.processor == ProcessorType::Default && payload.fallbacks.is_empty() {
         db::delete_feed_processor(&db, id)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     } else {
-        db::save_feed_processor(&db, id, payload.processor, payload.custom_config.as_deref())
+        db::save_feed_processor(&db, id, payload.processor, payload.custom_config.as_deref(), &payload.fallbacks)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     }
     
     Ok(StatusCode::OK)
 }
 
+pub async fn get_feed_filter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Option<FeedFilter>>, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let filter = db::get_feed_filter(&db, id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(filter))
+}
+
+pub async fn update_feed_filter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(payload): Json<FeedFilter>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    CompiledFilter::new(&payload)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter pattern: {}", e)))?;
+
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+
+    if payload.include.is_empty() && payload.exclude.is_empty() {
+        db::delete_feed_filter(&db, id)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    } else {
+        db::save_feed_filter(&db, id, &payload)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn get_feed_auth(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Option<FeedAuth>>, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    let auth = db::get_feed_auth(&db, id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Secrets are write-only: report which ones are set, but not their values.
+    Ok(Json(auth.map(|mut a| {
+        a.password = a.password.map(|_| "".to_string());
+        a.bearer_token = a.bearer_token.map(|_| "".to_string());
+        a.headers.values_mut().for_each(|v| v.clear());
+        a
+    })))
+}
+
+pub async fn update_feed_auth(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut payload): Json<FeedAuth>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+
+    // Empty secrets mean "unchanged", as returned by `get_feed_auth`.
+    if let Ok(Some(existing)) = db::get_feed_auth(&db, id) {
+        if payload.password.as_deref() == Some("") {
+            payload.password = existing.password;
+        }
+        if payload.bearer_token.as_deref() == Some("") {
+            payload.bearer_token = existing.bearer_token;
+        }
+        for (name, value) in payload.headers.iter_mut() {
+            if value.is_empty()
+                && let Some(existing_value) = existing.headers.get(name)
+            {
+                *value = existing_value.clone();
+            }
+        }
+    }
+    util::http::validate_feed_auth(&payload).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    db::save_feed_auth(&db, id, &payload)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn delete_feed_auth(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db = state.db.lock().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DB lock failed".to_string(),
+        )
+    })?;
+    db::delete_feed_auth(&db, id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn export_opml(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let feeds = {
+        let db = state.db.lock().map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "DB lock failed".to_string(),
+            )
+        })?;
+        db::get_feeds(&db).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/x-opml; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"rsspub_feeds.opml\""),
+        ],
+        subscriptions::export_opml(&feeds),
+    ))
+}
+
 pub async fn import_opml(
     State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
@@ -132,7 +401,7 @@ pub async fn import_opml(
                 )
             })?;
 
-            let document = opml::OPML::from_str(&opml_str).map_err(|e| {
+            let opml_feeds = subscriptions::parse_opml(&opml_str).map_err(|e| {
                 (
                     StatusCode::BAD_REQUEST,
                     format!("Failed to parse OPML: {}", e),
@@ -146,16 +415,28 @@ pub async fn import_opml(
                 )
             })?;
 
-            for outline in document.body.outlines {
-                if let Some(xml_url) = outline.xml_url {
-                    let _ = db::add_feed(&db, &xml_url, Some(&outline.text), 0);
+            for opml_feed in opml_feeds {
+                let _ = db::add_feed(
+                    &db,
+                    &opml_feed.url,
+                    opml_feed.name.as_deref(),
+                    0,
+                    None,
+                    None,
+                    opml_feed.category.as_deref(),
+                );
+                // Feeds that already existed keep their settings unless the file carries its own.
+                let Ok(Some(feed_id)) = db::get_feed_id_by_url(&db, &opml_feed.url) else { continue };
+                if let Some(category) = &opml_feed.category {
+                    let _ = db::update_feed_category(&db, feed_id, Some(category));
                 }
-
-                if !outline.outlines.is_empty() {
-                    for child in outline.outlines {
-                        if let Some(xml_url) = child.xml_url {
-                            let _ = db::add_feed(&db, &xml_url, Some(&child.text), 0);
+                if let Some(processor) = opml_feed.processor {
+                    let custom_config = opml_feed.custom_config.as_deref();
+                    match content_extractors::validate_processor(processor, &opml_feed.fallbacks, custom_config) {
+                        Ok(()) => {
+                            let _ = db::save_feed_processor(&db, feed_id, processor, custom_config, &opml_feed.fallbacks);
                         }
+                        Err(e) => tracing::warn!("Ignoring processor for {}: {}", opml_feed.url, e),
                     }
                 }
             }
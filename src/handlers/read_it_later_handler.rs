@@ -108,22 +108,7 @@ pub async fn deliver_read_it_later(
     let db_clone = state.db.clone();
     tokio::spawn(async move {
         info!("Starting background Read It Later EPUB generation...");
-        let image_timeout = {
-            match db_clone.lock() {
-                Ok(conn) => match db::get_general_config(&conn) {
-                    Ok(cfg) => cfg.image_timeout_seconds,
-                    Err(e) => {
-                        tracing::error!("Failed to fetch config, using default timeout: {}", e);
-                        45
-                    }
-                },
-                Err(_) => {
-                    tracing::error!("Failed to lock DB for config, using default timeout");
-                    45
-                }
-            }
-        };
-        match processor::generate_read_it_later_epub(articles, util::EPUB_OUTPUT_DIR, image_timeout)
+        match processor::generate_read_it_later_epub(articles, util::EPUB_OUTPUT_DIR)
             .await
         {
             Ok(filename) => {
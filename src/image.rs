@@ -2,25 +2,21 @@ use anyhow::Context;
 use anyhow::Result;
 use libvips::{ops, VipsImage};
 use regex::Regex;
-use reqwest::Client;
 use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use tempfile::NamedTempFile;
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use tracing::{error, info, warn};
+use crate::util::http;
 
-pub async fn process_images(html: &str) -> (String, Vec<(String, NamedTempFile, String)>) {
+pub async fn process_images(html: &str, retries: &Arc<AtomicU32>) -> (String, Vec<(String, NamedTempFile, String)>) {
     let mut processed_html = html.to_string();
     let mut images = Vec::new();
 
     let img_regex = Regex::new(r#"<img[^>]+src="([^"]+)"[^>]*>"#).unwrap();
 
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .build()
-        .unwrap_or_else(|_| Client::new());
-
     let mut matches = Vec::new();
     for cap in img_regex.captures_iter(html) {
         if let Some(src) = cap.get(1) {
@@ -35,15 +31,15 @@ pub async fn process_images(html: &str) -> (String, Vec<(String, NamedTempFile,
     let mut join_set = JoinSet::new();
 
     for (i, src) in matches.into_iter().enumerate() {
-        let client = client.clone();
         let src_clone = src.clone();
 
         let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let retries = Arc::clone(retries);
 
         join_set.spawn(async move {
             let _permit = permit;
             info!("Processing image: {}", &src_clone);
-            match download_image(&client, &src_clone).await {
+            match download_image(&src_clone, &retries).await {
                 Ok(temp_file) => {
                     let file_path = temp_file.path().to_str().unwrap().to_string();
 
@@ -90,8 +86,16 @@ pub async fn process_images(html: &str) -> (String, Vec<(String, NamedTempFile,
     (processed_html, images)
 }
 
-async fn download_image(client: &Client, url: &str) -> Result<NamedTempFile> {
-    let mut resp = client.get(url).send().await?;
+async fn download_image(url: &str, retry_count: &AtomicU32) -> Result<NamedTempFile> {
+    let client = http::client_for(url, None)?;
+    let request = client.get(url).timeout(http::timeout_for(http::RequestKind::Image));
+    let request = crate::util::cookies::with_domain_cookies(request, url);
+    let (resp, retries) = crate::util::http::send_with_retry(request).await;
+    if retries > 0 {
+        warn!("Image {} needed {} retries", url, retries);
+        retry_count.fetch_add(retries, Ordering::Relaxed);
+    }
+    let mut resp = resp?;
     let mut temp_file = NamedTempFile::new()?;
 
     while let Some(chunk) = resp.chunk().await? {
@@ -1,33 +1,27 @@
 use crate::epub_message::{CompletionMessage, EpubPart};
+use crate::util::http;
 use anyhow::Result;
 use image::ImageFormat;
 use regex::Regex;
-use reqwest::Client;
 use std::any::Any;
 use std::io::Cursor;
-use std::sync::LazyLock;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, LazyLock};
 use tokio::sync::mpsc::Sender;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 pub async fn process_images(
     html: &str,
     tx_m: &Sender<CompletionMessage>,
     seq_id: &usize,
-    timeout_seconds: u64,
+    retries: &Arc<AtomicU32>,
 ) -> (String, usize) {
     let mut processed_html = html.to_string();
 
     static IMG_REGEX: LazyLock<Regex> =
         LazyLock::new(|| Regex::new(r#"<img[^>]+src="([^"]+)"[^>]*>"#).unwrap());
 
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(Duration::from_secs(timeout_seconds))
-        .build()
-        .unwrap_or_else(|_| Client::new());
-
     let mut matches = Vec::new();
     //TODO: compare performance regex vs dom_query
     for cap in IMG_REGEX.captures_iter(html) {
@@ -40,7 +34,6 @@ pub async fn process_images(
     matches.dedup();
     let total_images = matches.len();
     for (i, src) in matches.into_iter().enumerate() {
-        let client = client.clone();
         let src_clone = src.clone();
         let extension = "jpg";
         let uuid = Uuid::new_v4();
@@ -48,9 +41,10 @@ pub async fn process_images(
         processed_html = processed_html.replace(&src, &filename);
         let tx_m = tx_m.clone();
         let sq = *seq_id;
+        let retries = Arc::clone(retries);
         tokio::spawn(async move {
             info!("Processing image: {}", src_clone);
-            match download_image(&client, &src_clone).await
+            match download_image(&src_clone, &retries).await
             {
                     Ok((img_data, format)) => match resize_and_grayscale(img_data, format).await {
                         Ok(processed_data) => {
@@ -107,8 +101,15 @@ async fn send_empty_message_on_error(i: usize, tx_m: Sender<CompletionMessage>,
     return Err("Failed");
 }
 
-async fn download_image(client: &Client, url: &str) -> Result<(Vec<u8>, ImageFormat)> {
-    let resp = client.get(url).send().await?;
+async fn download_image(url: &str, retry_count: &AtomicU32) -> Result<(Vec<u8>, ImageFormat)> {
+    let client = http::client_for(url, None)?;
+    let request = crate::util::cookies::with_domain_cookies(client.get(url).timeout(http::timeout_for(http::RequestKind::Image)), url);
+    let (resp, retries) = crate::util::http::send_with_retry(request).await;
+    if retries > 0 {
+        warn!("Image {} needed {} retries", url, retries);
+        retry_count.fetch_add(retries, Ordering::Relaxed);
+    }
+    let resp = resp?;
     //let _content_length = &resp.content_length().unwrap_or(0);
     let bytes = resp.bytes().await?.to_vec();
 
@@ -1,8 +1,10 @@
 mod db;
+mod discovery;
 mod email;
 mod epub_gen;
 mod epub_message;
 mod feed;
+mod filters;
 #[cfg(feature = "mem_opt")]
 mod image;
 #[cfg(not(feature = "mem_opt"))]
@@ -12,6 +14,7 @@ mod models;
 mod opds;
 mod processor;
 mod scheduler;
+mod subscriptions;
 mod util;
 mod handlers;
 mod routes;
@@ -37,7 +40,15 @@ async fn main() {
     tracing_subscriber::fmt().init();
 
     let conn = schema_init::init_db("./db/rpub.db").expect("Failed to initialize database");
+    match db::get_domain_cookie_jars(&conn) {
+        Ok(jars) => util::cookies::refresh_domain_cookies(jars),
+        Err(e) => tracing::warn!("Failed to load domain cookies: {}", e),
+    }
+    if let Err(e) = util::http::reload_http_settings(&conn) {
+        tracing::warn!("Failed to load HTTP settings: {}", e);
+    }
     let db_mutex = Arc::new(Mutex::new(conn));
+    util::page_cache::init(db_mutex.clone());
     let sched = scheduler::init_scheduler(db_mutex.clone())
         .await
         .expect("Failed to initialize scheduler");
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use tokio::sync::Mutex as TokioMutex;
 use tokio_cron_scheduler::JobScheduler;
@@ -11,6 +12,52 @@ pub struct Feed {
     #[serde(default)]
     pub concurrency_limit: usize,
     pub feed_processor: ContentProcessor,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub last_delivered_at: Option<String>,
+    /// Per-feed lookback window, overriding `GeneralConfig::fetch_since_hours`.
+    #[serde(default)]
+    pub fetch_since_hours: Option<i32>,
+    /// Only the newest `max_items` entries of this feed go into a digest.
+    #[serde(default)]
+    pub max_items: Option<usize>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub health: FeedHealth,
+    #[serde(default)]
+    pub filter: Option<FeedFilter>,
+    /// Credentials are never sent back in feed listings.
+    #[serde(skip)]
+    pub auth: Option<FeedAuth>,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Position in the user's feed order, lowest first.
+    #[serde(default)]
+    pub priority: i64,
+    /// Overrides the global background poll interval for this feed.
+    #[serde(default)]
+    pub poll_interval_minutes: Option<i32>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FeedHealth {
+    pub last_attempt_at: Option<String>,
+    pub last_success_at: Option<String>,
+    pub consecutive_failures: i32,
+    pub last_http_status: Option<u16>,
+    pub last_error: Option<String>,
+    /// Retries needed by the most recent fetch.
+    pub last_retry_count: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -46,6 +93,9 @@ pub struct AppState {
 pub struct GenerateRequest {
     #[serde(default)]
     pub feeds: Vec<Feed>,
+    /// Limits stored feeds to these categories (sub-categories included).
+    #[serde(default)]
+    pub categories: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -57,6 +107,79 @@ pub struct AddFeedRequest {
     #[serde(default)]
     pub processor: Option<ProcessorType>,
     pub custom_config: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
+    #[serde(default)]
+    pub fetch_since_hours: Option<i32>,
+    #[serde(default)]
+    pub max_items: Option<usize>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// Partial feed update: a missing field keeps its stored value, an explicit `null` clears it.
+#[derive(Deserialize, Default)]
+pub struct UpdateFeedRequest {
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub name: Option<Option<String>>,
+    #[serde(default)]
+    pub concurrency_limit: Option<usize>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub fetch_since_hours: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub max_items: Option<Option<usize>>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub proxy_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub category: Option<Option<String>>,
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub poll_interval_minutes: Option<Option<i32>>,
+}
+
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+pub struct FeedOrderRequest {
+    pub feed_ids: Vec<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CategorySummary {
+    pub name: String,
+    pub feed_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct RenameCategoryRequest {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Deserialize)]
+pub struct DiscoverFeedRequest {
+    pub url: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FeedCandidate {
+    pub url: String,
+    pub title: Option<String>,
+    pub kind: String,
+}
+
+#[derive(Serialize)]
+pub struct DiscoverFeedResponse {
+    /// Set when discovery settled on a single feed.
+    pub feed_url: Option<String>,
+    pub candidates: Vec<FeedCandidate>,
 }
 
 #[derive(Serialize)]
@@ -107,12 +230,204 @@ pub struct GeneralConfig {
     pub fetch_since_hours: i32,
     #[serde(default = "default_timeout")]
     pub image_timeout_seconds: i32,
+    #[serde(default = "default_feed_concurrency")]
+    pub feed_concurrency: i32,
+    #[serde(default = "default_feed_concurrency_per_host")]
+    pub feed_concurrency_per_host: i32,
+    #[serde(default)]
+    pub fetch_mode: FetchMode,
+    /// Disable a feed after this many failed fetches in a row, 0 keeps it enabled.
+    #[serde(default)]
+    pub auto_disable_after_failures: i32,
+    /// `http://`, `https://` or `socks5://` proxy for all outbound requests.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// Comma-separated hosts that are always fetched directly.
+    #[serde(default)]
+    pub proxy_bypass: Option<String>,
+    /// Total tries for a request that fails with a timeout, 429 or 5xx; 1 disables retrying.
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: i32,
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: i32,
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub retry_max_delay_ms: i32,
+    /// Falls back to a desktop browser user agent when unset.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub accept_language: Option<String>,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_seconds: i32,
+    /// Longest silence while receiving a response; the per-kind timeouts cap the whole request.
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout_seconds: i32,
+    #[serde(default = "default_timeout")]
+    pub feed_timeout_seconds: i32,
+    #[serde(default = "default_timeout")]
+    pub article_timeout_seconds: i32,
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: i32,
+    #[serde(default)]
+    pub digest_layout: DigestLayout,
+    #[serde(default)]
+    pub article_order: ArticleOrder,
+    /// Minutes between background feed polls. Digests are built from the polled articles
+    /// while this is set; 0 fetches everything at generation time instead.
+    #[serde(default)]
+    pub poll_interval_minutes: i32,
+    /// How long fetched pages and extracted articles are reused, 0 disables the cache.
+    #[serde(default = "default_cache_ttl_hours")]
+    pub cache_ttl_hours: i32,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        GeneralConfig {
+            fetch_since_hours: 24,
+            image_timeout_seconds: default_timeout(),
+            feed_concurrency: default_feed_concurrency(),
+            feed_concurrency_per_host: default_feed_concurrency_per_host(),
+            fetch_mode: FetchMode::default(),
+            auto_disable_after_failures: 0,
+            proxy_url: None,
+            proxy_bypass: None,
+            retry_attempts: default_retry_attempts(),
+            retry_base_delay_ms: default_retry_base_delay_ms(),
+            retry_max_delay_ms: default_retry_max_delay_ms(),
+            user_agent: None,
+            accept_language: None,
+            connect_timeout_seconds: default_connect_timeout(),
+            read_timeout_seconds: default_read_timeout(),
+            feed_timeout_seconds: default_timeout(),
+            article_timeout_seconds: default_timeout(),
+            max_redirects: default_max_redirects(),
+            digest_layout: DigestLayout::default(),
+            article_order: ArticleOrder::default(),
+            poll_interval_minutes: 0,
+            cache_ttl_hours: default_cache_ttl_hours(),
+        }
+    }
+}
+
+fn default_cache_ttl_hours() -> i32 {
+    24
+}
+
+fn default_connect_timeout() -> i32 {
+    10
+}
+
+fn default_read_timeout() -> i32 {
+    30
+}
+
+fn default_max_redirects() -> i32 {
+    10
+}
+
+fn default_retry_attempts() -> i32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> i32 {
+    500
+}
+
+fn default_retry_max_delay_ms() -> i32 {
+    30_000
 }
 
 fn default_timeout() -> i32 {
     45
 }
 
+fn default_feed_concurrency() -> i32 {
+    8
+}
+
+fn default_feed_concurrency_per_host() -> i32 {
+    2
+}
+
+/// How the digest decides which feed items are new.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchMode {
+    /// Items published within the last `fetch_since_hours`.
+    #[default]
+    Window = 1,
+    /// Items published since the feed was last delivered, falling back to the window on first run.
+    SinceLastDelivery = 2,
+}
+
+impl FetchMode {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            2 => FetchMode::SinceLastDelivery,
+            _ => FetchMode::Window,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        self as i32
+    }
+}
+
+/// How the digest's table of contents is organised.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestLayout {
+    /// One contents page per source, each listing its articles.
+    #[default]
+    BySource = 1,
+    /// Category pages listing their sources, each of which lists its articles.
+    ByCategory = 2,
+}
+
+impl DigestLayout {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            2 => DigestLayout::ByCategory,
+            _ => DigestLayout::BySource,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Order of the sections and chapters within a digest.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArticleOrder {
+    /// Sections by name, newest article first.
+    #[default]
+    NewestFirst = 1,
+    /// Sections by name, oldest article first for catching up.
+    OldestFirst = 2,
+    /// Sections in the user's feed order, newest article first.
+    FeedPriority = 3,
+    /// Chapters take turns across sources in feed order.
+    RoundRobin = 4,
+}
+
+impl ArticleOrder {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            2 => ArticleOrder::OldestFirst,
+            3 => ArticleOrder::FeedPriority,
+            4 => ArticleOrder::RoundRobin,
+            _ => ArticleOrder::NewestFirst,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        self as i32
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ProcessorType {
@@ -120,6 +435,8 @@ pub enum ProcessorType {
     DomSmoothie = 2,
     Custom = 3,
     TextOnly = 4,
+    /// Build the chapter from the content embedded in the feed instead of scraping the page.
+    FeedContent = 5,
 }
 
 impl Default for ProcessorType {
@@ -134,6 +451,7 @@ impl ProcessorType {
             2 => ProcessorType::DomSmoothie,
             3 => ProcessorType::Custom,
             4 => ProcessorType::TextOnly,
+            5 => ProcessorType::FeedContent,
             _ => ProcessorType::Default,
         }
     }
@@ -141,6 +459,11 @@ impl ProcessorType {
     pub fn to_i32(self) -> i32 {
         self as i32
     }
+
+    /// True for processors that need the article page to be downloaded.
+    pub fn scrapes_page(self) -> bool {
+        self != ProcessorType::FeedContent
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -148,6 +471,22 @@ pub struct ContentProcessor {
     pub id: Option<i64>,
     pub processor: ProcessorType,
     pub custom_config: Option<String>,
+    /// Processors tried in order when the previous one yields empty or paywalled content.
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
+}
+
+impl ContentProcessor {
+    /// The processor followed by its fallbacks, each listed once.
+    pub fn chain(&self) -> Vec<ProcessorType> {
+        let mut chain = vec![self.processor];
+        for step in &self.fallbacks {
+            if !chain.contains(step) {
+                chain.push(*step);
+            }
+        }
+        chain
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -171,6 +510,52 @@ pub struct CustomExtractorConfig {
     pub discard: Vec<String>,
     #[serde(default)]
     pub output_mode: OutputMode,
+    /// CSS selector for the link to an article's next page, used instead of `rel="next"`.
+    #[serde(default)]
+    pub next_page: Option<String>,
+    /// Most pages fetched for one article, including the first.
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+    Title,
+    Author,
+    Category,
+    Content,
+    Any,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterRule {
+    pub field: FilterField,
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression instead of a plain keyword.
+    #[serde(default)]
+    pub regex: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FeedFilter {
+    #[serde(default)]
+    pub include: Vec<FilterRule>,
+    #[serde(default)]
+    pub exclude: Vec<FilterRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FeedAuth {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    /// Extra request headers, e.g. `PRIVATE-TOKEN` for GitLab.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -180,6 +565,51 @@ pub struct DomainOverride {
     pub processor: ProcessorType,
     pub custom_config: Option<String>,
     pub created_at: String,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
+}
+
+/// An uploaded cookie jar; the cookie values themselves are never returned.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomainCookies {
+    pub id: Option<i64>,
+    pub domain: String,
+    pub cookie_count: usize,
+    pub created_at: String,
+}
+
+/// A page to run a processor against, either fetched from `url` or given as `html`.
+#[derive(Deserialize)]
+pub struct ExtractPreviewRequest {
+    pub url: Option<String>,
+    pub html: Option<String>,
+    pub processor: ProcessorType,
+    pub custom_config: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
+    /// Stands in for the feed's own content when the chain includes `feed_content`.
+    #[serde(default)]
+    pub feed_content: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ExtractPreviewResponse {
+    pub title: String,
+    pub content: String,
+    pub byline: Option<String>,
+    pub excerpt: Option<String>,
+    pub lead_image: Option<String>,
+    /// The step of the processor chain whose output was used.
+    pub step: ProcessorType,
+    /// Steps that were skipped, with the reason.
+    pub rejected: Vec<String>,
+    /// Time spent fetching the page, unset when the HTML was supplied.
+    pub fetch_ms: Option<u64>,
+    pub extract_ms: u64,
+    pub next_page: Option<String>,
+    pub warnings: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -187,4 +617,8 @@ pub struct AddDomainOverrideRequest {
     pub domain: String,
     pub processor: ProcessorType,
     pub custom_config: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Vec<ProcessorType>,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
 }
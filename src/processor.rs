@@ -1,59 +1,455 @@
-use crate::models::{Feed, ReadItLaterArticle};
+use crate::models::{ArticleOrder, DigestLayout, Feed, FetchMode, GeneralConfig, ReadItLaterArticle};
 use crate::{epub_gen, feed};
 use anyhow::Result;
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
-use reqwest::Client;
 use rusqlite::Connection;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
 use tracing::{info, warn};
 use crate::feed::Article;
 use crate::util::content_extractors;
+use crate::util::http;
+
+const SEEN_ITEM_RETENTION_DAYS: i64 = 30;
 
 pub async fn generate_epub(
     feeds: Vec<Feed>,
     _db: &Arc<Mutex<Connection>>,
     output_path: &str,
 ) -> Result<()> {
+    let feeds: Vec<Feed> = feeds.into_iter().filter(|f| f.enabled).collect();
+
+    let config = {
+        let conn = _db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
+        crate::db::get_general_config(&conn)?
+    };
+
+    // Feeds passed in ad hoc were never polled, so they are always fetched live.
+    if config.poll_interval_minutes > 0 && feeds.iter().all(|f| f.id.is_some()) {
+        return generate_epub_from_store(&feeds, _db, output_path, &config).await;
+    }
     info!("Fetching {} feeds...", feeds.len());
 
-    let (fetched_feeds, errors) = feed::fetch_feeds(&feeds).await;
+    let started_at = Utc::now();
+    let (mut fetched_feeds, errors, statuses) = feed::fetch_feeds(
+        &feeds,
+        config.feed_concurrency.max(1) as usize,
+        config.feed_concurrency_per_host.max(1) as usize,
+    )
+    .await;
+    record_feed_health(_db, &statuses, config.auto_disable_after_failures);
+    let cache_headers: Vec<(i64, Option<String>, Option<String>)> = fetched_feeds
+        .iter()
+        .filter_map(|f| f.feed_id.map(|id| (id, f.etag.clone(), f.last_modified.clone())))
+        .collect();
 
-    let (since, image_timeout) = {
+    let since = Utc::now() - ChronoDuration::hours(config.fetch_since_hours as i64);
+    {
         let conn = _db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
-        let config = crate::db::get_general_config(&conn)?;
-        (Utc::now() - ChronoDuration::hours(config.fetch_since_hours as i64), config.image_timeout_seconds)
-    };
+        prepare_fetched_feeds(&conn, &mut fetched_feeds, &feeds, &config, started_at, false)?;
+    }
     info!("Filtering items since: {}", since);
-    let articles = feed::filter_items(fetched_feeds, errors, since).await;
+    let articles = feed::order_articles(
+        feed::filter_items(fetched_feeds, errors, since).await,
+        config.article_order,
+    );
+    record_extra_retries(_db, article_retries(&articles));
 
     if articles.is_empty() {
-        return Err(anyhow::anyhow!("No articles found in the last 24 hours."));
+        save_feed_cache_headers(_db, &cache_headers);
+        return Err(anyhow::anyhow!("No new articles found."));
+    }
+
+    let image_retries = generate_epub_from_articles(output_path, &articles, config.digest_layout, config.article_order)
+        .await?;
+    record_extra_retries(_db, image_retries);
+    save_feed_cache_headers(_db, &cache_headers);
+    let delivered_feed_ids: Vec<i64> = cache_headers.iter().map(|(id, _, _)| *id).collect();
+    record_delivery(_db, &articles, &[], &delivered_feed_ids, started_at, config.fetch_since_hours);
+
+    Ok(())
+}
+
+/// Sets each fetched feed's cutoff and the items to skip. Polling also skips items already stored.
+fn prepare_fetched_feeds(
+    conn: &Connection,
+    fetched_feeds: &mut [feed::FeedWrapper],
+    feeds: &[Feed],
+    config: &GeneralConfig,
+    now: DateTime<Utc>,
+    skip_stored: bool,
+) -> Result<()> {
+    let since = now - ChronoDuration::hours(config.fetch_since_hours as i64);
+    for fetched in fetched_feeds.iter_mut() {
+        assign_first_seen_dates(conn, fetched, now)?;
+        let feed_since = fetched
+            .fetch_since_hours
+            .map(|hours| now - ChronoDuration::hours(hours as i64))
+            .unwrap_or(since);
+        fetched.since = Some(feed_since);
+        let Some(feed_id) = fetched.feed_id else { continue };
+        fetched.seen = crate::db::get_seen_item_keys(conn, feed_id)?;
+        if skip_stored {
+            fetched.seen.extend(crate::db::get_stored_item_keys(conn, feed_id)?);
+        }
+        if config.fetch_mode == FetchMode::SinceLastDelivery
+            && let Some(last_delivered) = feeds
+                .iter()
+                .find(|f| f.id == Some(feed_id))
+                .and_then(|f| f.last_delivered_at.as_deref())
+                .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+        {
+            fetched.since = Some(last_delivered.with_timezone(&Utc).min(feed_since));
+        }
     }
+    Ok(())
+}
+
+/// Builds the digest from articles the background poller already stored, without touching the network
+/// for anything but images.
+async fn generate_epub_from_store(
+    feeds: &[Feed],
+    db: &Arc<Mutex<Connection>>,
+    output_path: &str,
+    config: &GeneralConfig,
+) -> Result<()> {
+    let started_at = Utc::now();
+    let stored = {
+        let conn = db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
+        crate::db::get_undelivered_articles(&conn)?
+    };
+
+    let (articles, trimmed) = select_stored_articles(stored, feeds, config, started_at);
+    info!("Building digest from {} stored articles", articles.len());
 
-    generate_epub_from_articles(output_path, &articles, image_timeout).await?;
+    // Poll failures are kept in feed health; a feed whose last poll failed is reported like a live fetch error.
+    let errors: Vec<(String, String)> = feeds
+        .iter()
+        .filter(|f| f.health.consecutive_failures > 0)
+        .filter_map(|f| f.health.last_error.clone().map(|e| (f.url.clone(), e)))
+        .collect();
+    let mut articles = feed::dedupe_articles(articles);
+    articles.extend(feed::error_articles(errors));
+    let articles = feed::order_articles(articles, config.article_order);
+    if articles.is_empty() {
+        return Err(anyhow::anyhow!("No new articles found."));
+    }
 
+    let image_retries =
+        generate_epub_from_articles(output_path, &articles, config.digest_layout, config.article_order).await?;
+    record_extra_retries(db, image_retries);
+    let feed_ids: Vec<i64> = feeds.iter().filter_map(|f| f.id).collect();
+    record_delivery(db, &articles, &trimmed, &feed_ids, started_at, config.fetch_since_hours);
     Ok(())
 }
 
-async fn generate_epub_from_articles(output_path: &str, articles: &Vec<Article>, image_timeout: i32) -> Result<()> {
+/// Applies each feed's window and `max_items` to stored articles, which arrive newest first.
+/// Returns the selected articles and the keys of those trimmed by the window or `max_items`,
+/// which are recorded with the digest so they are not reloaded every time. Articles of feeds
+/// not in `feeds` are left for a later digest.
+fn select_stored_articles(
+    stored: Vec<Article>,
+    feeds: &[Feed],
+    config: &GeneralConfig,
+    now: DateTime<Utc>,
+) -> (Vec<Article>, Vec<(i64, String)>) {
+    let since = now - ChronoDuration::hours(config.fetch_since_hours as i64);
+    let mut per_feed: HashMap<i64, usize> = HashMap::new();
+    let mut selected = Vec::new();
+    let mut trimmed = Vec::new();
+    for article in stored {
+        let Some(feed) = feeds.iter().find(|f| f.id == article.feed_id) else { continue };
+        // Polling already applied the window, so since-last-delivery takes everything undelivered.
+        let in_window = config.fetch_mode != FetchMode::Window || {
+            let cutoff = feed
+                .fetch_since_hours
+                .map(|hours| now - ChronoDuration::hours(hours as i64))
+                .unwrap_or(since);
+            article.pub_date >= cutoff
+        };
+        let within_limit = in_window && {
+            let count = per_feed.entry(feed.id.unwrap_or_default()).or_default();
+            *count += 1;
+            feed.max_items.filter(|n| *n > 0).is_none_or(|max| *count <= max)
+        };
+        if within_limit {
+            selected.push(article);
+        } else if let Some(key) = article.feed_id.zip(article.item_key) {
+            trimmed.push(key);
+        }
+    }
+    (selected, trimmed)
+}
+
+static POLL_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears `POLL_RUNNING` when the poll ends, even if it panics or its task is cancelled.
+struct PollGuard;
+
+impl Drop for PollGuard {
+    fn drop(&mut self) {
+        POLL_RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// Fetches every feed whose poll interval has elapsed and stores its new articles for the next digest.
+pub async fn poll_due_feeds(db: &Arc<Mutex<Connection>>) -> Result<()> {
+    if POLL_RUNNING.swap(true, Ordering::AcqRel) {
+        info!("Previous feed poll still running, skipping");
+        return Ok(());
+    }
+    let _guard = PollGuard;
+    poll_feeds(db).await
+}
+
+async fn poll_feeds(db: &Arc<Mutex<Connection>>) -> Result<()> {
+    let (config, feeds) = {
+        let conn = db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
+        (crate::db::get_general_config(&conn)?, crate::db::get_feeds(&conn)?)
+    };
+    if config.poll_interval_minutes <= 0 {
+        return Ok(());
+    }
+
+    let started_at = Utc::now();
+    let due: Vec<Feed> = feeds
+        .into_iter()
+        .filter(|f| f.enabled && is_due_for_poll(f, config.poll_interval_minutes, started_at))
+        .collect();
+    if due.is_empty() {
+        return Ok(());
+    }
+    info!("Polling {} feeds...", due.len());
+
+    // Fetch errors reach the digest through the feed health recorded here.
+    let (mut fetched_feeds, _errors, statuses) = feed::fetch_feeds(
+        &due,
+        config.feed_concurrency.max(1) as usize,
+        config.feed_concurrency_per_host.max(1) as usize,
+    )
+    .await;
+    record_feed_health(db, &statuses, config.auto_disable_after_failures);
+    let cache_headers: Vec<(i64, Option<String>, Option<String>)> = fetched_feeds
+        .iter()
+        .filter_map(|f| f.feed_id.map(|id| (id, f.etag.clone(), f.last_modified.clone())))
+        .collect();
+
+    {
+        let conn = db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
+        prepare_fetched_feeds(&conn, &mut fetched_feeds, &due, &config, started_at, true)?;
+    }
+    let since = started_at - ChronoDuration::hours(config.fetch_since_hours as i64);
+    let articles = feed::filter_items(fetched_feeds, Vec::new(), since).await;
+    record_extra_retries(db, article_retries(&articles));
+
+    {
+        let conn = db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
+        let stored = crate::db::store_articles(&conn, &articles, started_at)?;
+        // Items dropped as duplicates of a stored article must not come back on the next poll.
+        let duplicates: Vec<(i64, String)> = articles.iter().flat_map(|a| a.duplicate_keys.iter().cloned()).collect();
+        crate::db::mark_items_seen(&conn, &duplicates, started_at)?;
+        info!("Stored {} new articles", stored);
+    }
+    // Once stored, the items are safe even if the next digest fails, so the validators can be kept.
+    save_feed_cache_headers(db, &cache_headers);
+    Ok(())
+}
+
+fn is_due_for_poll(feed: &Feed, default_minutes: i32, now: DateTime<Utc>) -> bool {
+    let minutes = feed.poll_interval_minutes.filter(|m| *m > 0).unwrap_or(default_minutes);
+    match feed
+        .health
+        .last_attempt_at
+        .as_deref()
+        .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+    {
+        Some(last) => now - last.with_timezone(&Utc) >= ChronoDuration::minutes(minutes as i64),
+        None => true,
+    }
+}
+
+// Entries without published/updated dates get the time they were first fetched,
+// so they fall inside the window once and are then remembered as delivered.
+fn assign_first_seen_dates(
+    conn: &Connection,
+    fetched: &mut feed::FeedWrapper,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    for entry in fetched.feed.entries.iter_mut() {
+        if entry.published.is_some() || entry.updated.is_some() {
+            continue;
+        }
+        let first_seen = match fetched.feed_id {
+            Some(feed_id) => crate::db::get_or_insert_first_seen(conn, feed_id, &feed::item_key(entry), now)?,
+            None => now,
+        };
+        entry.published = Some(first_seen);
+    }
+    Ok(())
+}
+
+/// Marks `articles` and the `trimmed` item keys delivered, then prunes records past the retention horizon.
+fn record_delivery(
+    db: &Arc<Mutex<Connection>>,
+    articles: &[Article],
+    trimmed: &[(i64, String)],
+    feed_ids: &[i64],
+    delivered_at: DateTime<Utc>,
+    fetch_since_hours: i32,
+) {
+    let conn = match db.lock() {
+        Ok(conn) => conn,
+        Err(_) => {
+            warn!("DB lock failed, delivered items not recorded");
+            return;
+        }
+    };
+    let items: Vec<(i64, String)> = articles
+        .iter()
+        .flat_map(|a| {
+            let own = a.feed_id.zip(a.item_key.clone());
+            own.into_iter().chain(a.duplicate_keys.iter().cloned())
+        })
+        .chain(trimmed.iter().cloned())
+        .collect();
+    if let Err(e) = crate::db::mark_items_seen(&conn, &items, delivered_at) {
+        warn!("Failed to record delivered items: {}", e);
+    }
+    if let Err(e) = crate::db::mark_stored_articles_delivered(&conn, &items, delivered_at) {
+        warn!("Failed to mark stored articles delivered: {}", e);
+    }
+    for id in feed_ids {
+        if let Err(e) = crate::db::update_feed_last_delivered(&conn, *id, delivered_at) {
+            warn!("Failed to update last delivery for feed {}: {}", id, e);
+        }
+    }
+
+    // Anything older than the widest fetch window can no longer be picked up again, so its record is not needed.
+    let widest_window = match crate::db::get_max_feed_fetch_since_hours(&conn) {
+        Ok(feed_max) => feed_max.unwrap_or(0).max(fetch_since_hours),
+        Err(e) => {
+            warn!("Failed to read per-feed fetch windows, skipping prune: {}", e);
+            return;
+        }
+    };
+    let retention = ChronoDuration::hours((widest_window as i64).max(SEEN_ITEM_RETENTION_DAYS * 24));
+    match crate::db::prune_seen_items(&conn, delivered_at - retention) {
+        Ok(removed) if removed > 0 => info!("Pruned {} old delivered item records", removed),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to prune delivered item records: {}", e),
+    }
+    match crate::db::prune_first_seen(&conn, delivered_at - retention) {
+        Ok(removed) if removed > 0 => info!("Pruned {} old first-seen records", removed),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to prune first-seen records: {}", e),
+    }
+    match crate::db::prune_stored_articles(&conn, delivered_at - retention) {
+        Ok(removed) if removed > 0 => info!("Pruned {} old stored articles", removed),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to prune stored articles: {}", e),
+    }
+}
+
+fn record_feed_health(
+    db: &Arc<Mutex<Connection>>,
+    statuses: &[feed::FetchStatus],
+    auto_disable_after_failures: i32,
+) {
+    let conn = match db.lock() {
+        Ok(conn) => conn,
+        Err(_) => {
+            warn!("DB lock failed, feed health not recorded");
+            return;
+        }
+    };
+    for status in statuses {
+        let Some(id) = status.feed_id else { continue };
+        match crate::db::record_feed_fetch(
+            &conn,
+            id,
+            status.http_status,
+            status.error.as_deref(),
+            status.retries,
+            auto_disable_after_failures,
+        ) {
+            Ok(true) => warn!("Feed {} disabled after {} consecutive failures", id, auto_disable_after_failures),
+            Ok(false) => {}
+            Err(e) => warn!("Failed to record health for feed {}: {}", id, e),
+        }
+    }
+}
+
+fn article_retries(articles: &[Article]) -> impl Iterator<Item = (i64, u32)> + '_ {
+    articles.iter().filter_map(|a| a.feed_id.map(|id| (id, a.retries)))
+}
+
+/// Adds the retries spent on a feed's article pages and images to the retries its own fetch needed.
+fn record_extra_retries(db: &Arc<Mutex<Connection>>, retries: impl IntoIterator<Item = (i64, u32)>) {
+    let mut by_feed: HashMap<i64, u32> = HashMap::new();
+    for (id, count) in retries {
+        *by_feed.entry(id).or_insert(0) += count;
+    }
+    by_feed.retain(|_, count| *count > 0);
+    if by_feed.is_empty() {
+        return;
+    }
+    let conn = match db.lock() {
+        Ok(conn) => conn,
+        Err(_) => {
+            warn!("DB lock failed, feed retries not recorded");
+            return;
+        }
+    };
+    for (id, count) in by_feed {
+        if let Err(e) = crate::db::add_feed_retries(&conn, id, count) {
+            warn!("Failed to record retries for feed {}: {}", id, e);
+        }
+    }
+}
+
+// Validators are only stored once the items they cover have been delivered,
+// otherwise a failed run would turn the next fetch into a 304 and lose them.
+fn save_feed_cache_headers(
+    db: &Arc<Mutex<Connection>>,
+    cache_headers: &[(i64, Option<String>, Option<String>)],
+) {
+    let conn = match db.lock() {
+        Ok(conn) => conn,
+        Err(_) => {
+            warn!("DB lock failed, feed cache headers not saved");
+            return;
+        }
+    };
+    for (id, etag, last_modified) in cache_headers {
+        if let Err(e) = crate::db::update_feed_cache_headers(&conn, *id, etag.as_deref(), last_modified.as_deref()) {
+            warn!("Failed to save cache headers for feed {}: {}", id, e);
+        }
+    }
+}
+
+async fn generate_epub_from_articles(
+    output_path: &str,
+    articles: &[Article],
+    layout: DigestLayout,
+    order: ArticleOrder,
+) -> Result<HashMap<i64, u32>> {
     let temp_path = get_temp_file_path(output_path);
     info!("Generating EPUB to temporary file: {:?}", temp_path);
     let file = std::fs::File::create(&temp_path)?;
 
-    match epub_gen::generate_epub_data(&articles, file, image_timeout).await {
-        Ok(_) => {
+    match epub_gen::generate_epub_data(articles, file, layout, order).await {
+        Ok(image_retries) => {
             info!("EPUB generation successful. moving to {}", output_path);
             std::fs::rename(&temp_path, output_path)?;
-            Ok(())
+            Ok(image_retries)
         }
         Err(e) => {
             let _ = std::fs::remove_file(&temp_path);
             Err(anyhow::anyhow!("Failed to generate EPUB: {}", e))
         }
-    }?;
-    Ok(())
+    }
 }
 
 fn get_temp_file_path(output_path: &str) -> PathBuf {
@@ -87,7 +483,6 @@ pub async fn generate_and_save(
 pub async fn generate_read_it_later_epub(
     articles: Vec<ReadItLaterArticle>,
     output_dir: &str,
-    image_timeout: i32,
 ) -> Result<String> {
     let filename = format!(
         "read_it_later_{}.epub",
@@ -97,37 +492,51 @@ pub async fn generate_read_it_later_epub(
 
     info!("Fetching content for {} Read It Later articles...", articles.len());
 
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(Duration::from_secs(45))
-        .cookie_store(true)
-        .build()
-        .unwrap_or_else(|_| Client::new());
-
     let mut fetched_articles = Vec::new();
 
-    fetch_all_article_with_content(articles, &client, &mut fetched_articles).await;
+    fetch_all_article_with_content(articles, &mut fetched_articles).await;
 
     if fetched_articles.is_empty() {
         return Err(anyhow::anyhow!("No content could be fetched."));
     }
-    generate_epub_from_articles(&filepath, &fetched_articles, image_timeout).await?;
+    generate_epub_from_articles(
+        &filepath,
+        &fetched_articles,
+        DigestLayout::BySource,
+        ArticleOrder::NewestFirst,
+    )
+    .await?;
     Ok(filename)
 }
 
-async fn fetch_all_article_with_content(articles: Vec<ReadItLaterArticle>, client: &Client, fetched_articles: &mut Vec<Article>) {
+async fn fetch_all_article_with_content(articles: Vec<ReadItLaterArticle>, fetched_articles: &mut Vec<Article>) {
     for article in articles {
         info!("Fetching: {}", article.url);
-        match content_extractors::fetch_full_content(&client, &article.url).await {
-            Ok((title, content)) => {
+        let fetched = match http::client_for(&article.url, None) {
+            Ok(client) => content_extractors::fetch_full_content(&client, &article.url).await,
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok(fetched) => {
                 fetched_articles.push(crate::feed::Article {
-                    title,
+                    title: fetched.title,
                     link: article.url.clone(),
-                    content,
+                    content: fetched.content,
                     pub_date: DateTime::parse_from_rfc3339(&article.created_at)
                         .map(|dt| dt.with_timezone(&Utc))
                         .unwrap_or_else(|_| Utc::now()),
                     source: "Read It Later".to_string(),
+                    category: None,
+                    priority: 0,
+                    feed_id: None,
+                    item_key: None,
+                    duplicate_keys: Vec::new(),
+                    author: fetched.byline,
+                    tags: Vec::new(),
+                    excerpt: fetched.excerpt,
+                    lead_image: fetched.lead_image,
+                    canonical_url: None,
+                    retries: fetched.retries,
                 });
             }
             Err(e) => {
@@ -138,8 +547,134 @@ async fn fetch_all_article_with_content(articles: Vec<ReadItLaterArticle>, clien
                     content: format!("<p>Failed to fetch content: {}</p>", e),
                     pub_date: Utc::now(),
                     source: "Read It Later Errors".to_string(),
+                    category: None,
+                    priority: 0,
+                    feed_id: None,
+                    item_key: None,
+                    duplicate_keys: Vec::new(),
+                    author: None,
+                    tags: Vec::new(),
+                    excerpt: None,
+                    lead_image: None,
+                    canonical_url: None,
+                    retries: 0,
                 });
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema_init;
+    use crate::models::UpdateFeedRequest;
+
+    fn feeds_with(conn: &Connection, updates: &[UpdateFeedRequest]) -> Vec<Feed> {
+        for (i, update) in updates.iter().enumerate() {
+            let url = format!("https://example{}.com/rss", i);
+            let id = crate::db::add_feed(conn, &url, None, 0, None, None, None).unwrap();
+            crate::db::update_feed(conn, id, update).unwrap();
+        }
+        crate::db::get_feeds(conn).unwrap()
+    }
+
+    fn stored(feed: &Feed, hours_ago: i64) -> Article {
+        Article {
+            title: format!("{} {}", feed.url, hours_ago),
+            link: String::new(),
+            content: String::new(),
+            pub_date: Utc::now() - ChronoDuration::hours(hours_ago),
+            source: feed.url.clone(),
+            category: None,
+            priority: 0,
+            feed_id: feed.id,
+            item_key: Some(hours_ago.to_string()),
+            duplicate_keys: Vec::new(),
+            author: None,
+            tags: Vec::new(),
+            excerpt: None,
+            lead_image: None,
+            canonical_url: None,
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_due_for_poll() {
+        let now = Utc::now();
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let mut feed = feeds_with(&conn, &[UpdateFeedRequest::default()]).remove(0);
+        assert!(is_due_for_poll(&feed, 60, now));
+
+        feed.health.last_attempt_at = Some((now - ChronoDuration::minutes(30)).to_rfc3339());
+        assert!(!is_due_for_poll(&feed, 60, now));
+        assert!(is_due_for_poll(&feed, 15, now));
+
+        feed.poll_interval_minutes = Some(120);
+        assert!(!is_due_for_poll(&feed, 15, now));
+        feed.poll_interval_minutes = Some(10);
+        assert!(is_due_for_poll(&feed, 60, now));
+    }
+
+    #[test]
+    fn test_select_stored_articles_applies_feed_limits() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let feeds = feeds_with(&conn, &[
+            UpdateFeedRequest { max_items: Some(Some(2)), ..Default::default() },
+            UpdateFeedRequest { fetch_since_hours: Some(Some(6)), ..Default::default() },
+        ]);
+        let (capped, short_window) = (&feeds[0], &feeds[1]);
+        let articles = vec![
+            stored(capped, 1),
+            stored(short_window, 2),
+            stored(capped, 3),
+            stored(capped, 4),
+            stored(short_window, 12),
+        ];
+        let config = GeneralConfig { fetch_mode: FetchMode::Window, ..Default::default() };
+
+        let (selected, trimmed) = select_stored_articles(articles.clone(), &feeds, &config, Utc::now());
+        let keys: Vec<_> = selected.iter().map(|a| (a.feed_id, a.item_key.clone().unwrap())).collect();
+        assert_eq!(
+            keys,
+            vec![(capped.id, "1".to_string()), (short_window.id, "2".to_string()), (capped.id, "3".to_string())]
+        );
+        let capped_id = capped.id.unwrap();
+        let short_window_id = short_window.id.unwrap();
+        assert_eq!(trimmed, vec![(capped_id, "4".to_string()), (short_window_id, "12".to_string())]);
+
+        // Since-last-delivery mode ignores the window but still caps the count.
+        let config = GeneralConfig { fetch_mode: FetchMode::SinceLastDelivery, ..Default::default() };
+        assert_eq!(select_stored_articles(articles, &feeds[..1], &config, Utc::now()).0.len(), 2);
+    }
+
+    #[test]
+    fn test_trimmed_and_stale_articles_are_not_kept() {
+        let conn = schema_init::init_db(":memory:").unwrap();
+        let feeds = feeds_with(&conn, &[
+            UpdateFeedRequest { max_items: Some(Some(1)), ..Default::default() },
+            UpdateFeedRequest::default(),
+        ]);
+        let now = Utc::now();
+        crate::db::store_articles(&conn, &[stored(&feeds[0], 1), stored(&feeds[0], 2)], now).unwrap();
+        // Stored long ago for a feed that every digest since has left out.
+        let stale_at = now - ChronoDuration::days(SEEN_ITEM_RETENTION_DAYS + 1);
+        crate::db::store_articles(&conn, &[stored(&feeds[1], 3)], stale_at).unwrap();
+        let first_seen_at = crate::db::get_or_insert_first_seen(&conn, feeds[1].id.unwrap(), "undated", stale_at).unwrap();
+        assert_eq!(first_seen_at, stale_at);
+        let db = Arc::new(Mutex::new(conn));
+
+        let pending = crate::db::get_undelivered_articles(&db.lock().unwrap()).unwrap();
+        let (selected, trimmed) = select_stored_articles(pending, &feeds[..1], &GeneralConfig::default(), now);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(trimmed.len(), 1);
+        record_delivery(&db, &selected, &trimmed, &[feeds[0].id.unwrap()], now, 24);
+
+        let conn = db.lock().unwrap();
+        assert!(crate::db::get_undelivered_articles(&conn).unwrap().is_empty());
+        assert!(crate::db::get_stored_item_keys(&conn, feeds[1].id.unwrap()).unwrap().is_empty());
+        let first_seen_at = crate::db::get_or_insert_first_seen(&conn, feeds[1].id.unwrap(), "undated", now).unwrap();
+        assert_eq!(first_seen_at, now);
+    }
+}
@@ -2,14 +2,14 @@ use crate::handlers;
 use crate::models::AppState;
 use axum::{
     http::{header, StatusCode},
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use std::sync::Arc;
 use tower::ServiceBuilder;
 use tower_http::{services::ServeDir, set_header::SetResponseHeaderLayer};
 use tracing::{info, warn};
-use crate::handlers::{auth_handler, config_handler, domain_override_handler, download_handler, email_handler, feed_handler, read_it_later_handler, schedule_handler};
+use crate::handlers::{auth_handler, config_handler, domain_cookie_handler, domain_override_handler, download_handler, email_handler, extract_handler, feed_handler, read_it_later_handler, schedule_handler};
 pub const RPUB_USERNAME: &'static str = "RPUB_USERNAME";
 pub const RPUB_PASSWORD: &'static str = "RPUB_PASSWORD";
 const SECURE_OPDS: &'static str = "SECURE_OPDS";
@@ -33,8 +33,20 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/generate", post(download_handler::generate_epub_adhoc))
         .route("/feeds", get(feed_handler::list_feeds).post(feed_handler::add_feed))
         .route("/feeds/import", post(feed_handler::import_opml))
-        .route("/feeds/{id}", delete(feed_handler::delete_feed))
+        .route("/feeds/export.opml", get(feed_handler::export_opml))
+        .route("/feeds/discover", post(feed_handler::discover_feed))
+        .route("/feeds/order", put(feed_handler::reorder_feeds))
+        .route("/categories", get(feed_handler::list_categories))
+        .route("/categories/rename", post(feed_handler::rename_category))
+        .route("/feeds/{id}", put(feed_handler::update_feed).delete(feed_handler::delete_feed))
         .route("/feeds/{id}/processor", get(feed_handler::get_feed_processor).put(feed_handler::update_feed_processor))
+        .route("/feeds/{id}/filters", get(feed_handler::get_feed_filter).put(feed_handler::update_feed_filter))
+        .route(
+            "/feeds/{id}/auth",
+            get(feed_handler::get_feed_auth)
+                .put(feed_handler::update_feed_auth)
+                .delete(feed_handler::delete_feed_auth),
+        )
         .route(
             "/schedules",
             get(schedule_handler::list_schedules).post(schedule_handler::add_schedule),
@@ -50,6 +62,7 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/general-config",
             get(config_handler::get_general_config).post(config_handler::update_general_config),
         )
+        .route("/cache", delete(config_handler::purge_cache))
         .route(
             "/read-it-later",
             get(read_it_later_handler::list_read_it_later).post(read_it_later_handler::add_read_it_later),
@@ -64,6 +77,12 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             get(domain_override_handler::list_domain_overrides).post(domain_override_handler::add_domain_override),
         )
         .route("/domain-overrides/{id}", delete(domain_override_handler::delete_domain_override))
+        .route("/extract/preview", post(extract_handler::preview_extraction))
+        .route(
+            "/domain-cookies",
+            get(domain_cookie_handler::list_domain_cookies).post(domain_cookie_handler::upload_domain_cookies),
+        )
+        .route("/domain-cookies/{id}", delete(domain_cookie_handler::delete_domain_cookies))
         .route("/auth/check", get(|| async { StatusCode::OK }));
 
     let protected_routes =add_auth_to_routes(protected_routes);
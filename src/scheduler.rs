@@ -16,10 +16,25 @@ pub async fn init_scheduler(db_conn: Arc<Mutex<Connection>>) -> Result<JobSchedu
             if let Err(e) = cleanup_old_files().await {
                 error!("Cleanup failed: {}", e);
             }
+            let pruned = crate::util::page_cache::prune_expired().await;
+            if pruned > 0 {
+                info!("Pruned {} expired cache entries", pruned);
+            }
         })
     })?;
     sched.add(cleanup_job).await?;
 
+    let poll_db = db_conn.clone();
+    let poll_job = Job::new_async("0 * * * * *", move |_uuid, _l| {
+        let db = poll_db.clone();
+        Box::pin(async move {
+            if let Err(e) = processor::poll_due_feeds(&db).await {
+                error!("Feed polling failed: {}", e);
+            }
+        })
+    })?;
+    sched.add(poll_job).await?;
+
     let schedules = {
         let conn = db_conn
             .lock()
@@ -86,13 +101,9 @@ async fn run_scheduled_generation(db: Arc<Mutex<Connection>>) -> Result<()> {
 }
 
 async fn run_read_it_later_generation(db: Arc<Mutex<Connection>>) -> Result<()> {
-    let (articles, image_timeout) = {
+    let articles = {
          let conn = db.lock().map_err(|_| anyhow::anyhow!("DB lock failed"))?;
-         let articles = db::get_read_it_later_articles(&conn, true)?;
-         let config = db::get_general_config(&conn)?;
-
-         
-         (articles, config.image_timeout_seconds)
+         db::get_read_it_later_articles(&conn, true)?
     };
 
     if articles.is_empty() {
@@ -101,7 +112,7 @@ async fn run_read_it_later_generation(db: Arc<Mutex<Connection>>) -> Result<()>
     }
     let article_ids: Vec<i64> = articles.iter().filter_map(|a| a.id).collect();
 
-    let filename = processor::generate_read_it_later_epub(articles, crate::util::EPUB_OUTPUT_DIR, image_timeout).await?;
+    let filename = processor::generate_read_it_later_epub(articles, crate::util::EPUB_OUTPUT_DIR).await?;
     info!("Read It Later generation completed: {}", filename);
 
 
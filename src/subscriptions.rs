@@ -0,0 +1,309 @@
+use crate::models::{Feed, ProcessorType};
+use crate::util::escape_xml;
+use chrono::Utc;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use std::collections::BTreeMap;
+
+/// Namespace for the outline attributes that carry RSSPub-specific feed settings.
+pub const RSSPUB_NAMESPACE: &str = "https://github.com/harshit181/RPub/opml";
+
+/// A feed read from an OPML file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpmlFeed {
+    pub url: String,
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub processor: Option<ProcessorType>,
+    pub custom_config: Option<String>,
+    pub fallbacks: Vec<ProcessorType>,
+}
+
+pub fn export_opml(feeds: &[Feed]) -> String {
+    let mut sorted: Vec<(Vec<&str>, &Feed)> = feeds
+        .iter()
+        .map(|feed| (category_segments(feed.category.as_deref()), feed))
+        .collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut body = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    for (segments, feed) in sorted {
+        let shared = open.iter().zip(&segments).take_while(|(a, b)| a == b).count();
+        while open.len() > shared {
+            open.pop();
+            body.push_str(&format!("{}</outline>\n", indent(open.len())));
+        }
+        for segment in &segments[shared..] {
+            body.push_str(&format!(
+                "{0}<outline text=\"{1}\" title=\"{1}\">\n",
+                indent(open.len()),
+                escape_attr(segment)
+            ));
+            open.push(segment);
+        }
+        body.push_str(&indent(open.len()));
+        body.push_str(&feed_outline(feed));
+        body.push('\n');
+    }
+    while !open.is_empty() {
+        open.pop();
+        body.push_str(&format!("{}</outline>\n", indent(open.len())));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0" xmlns:rsspub="{}">
+  <head>
+    <title>RSSPub Subscriptions</title>
+    <dateCreated>{}</dateCreated>
+  </head>
+  <body>
+{}  </body>
+</opml>
+"#,
+        RSSPUB_NAMESPACE,
+        Utc::now().to_rfc2822(),
+        body
+    )
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth + 2)
+}
+
+fn category_segments(category: Option<&str>) -> Vec<&str> {
+    category
+        .map(|c| c.split('/').map(str::trim).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Cleans up a `Parent/Child` category path; blank paths mean "no category".
+pub fn normalize_category(category: Option<&str>) -> Option<String> {
+    let segments = category_segments(category);
+    (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+/// True if `category` is `wanted` itself or one of its sub-categories.
+pub fn category_matches(category: Option<&str>, wanted: &str) -> bool {
+    let wanted = category_segments(Some(wanted));
+    let category = category_segments(category);
+    !wanted.is_empty() && category.starts_with(&wanted)
+}
+
+fn feed_outline(feed: &Feed) -> String {
+    let name = escape_attr(feed.name.as_deref().unwrap_or(&feed.url));
+    let mut outline = format!(
+        "<outline type=\"rss\" text=\"{0}\" title=\"{0}\" xmlUrl=\"{1}\"",
+        name,
+        escape_attr(&feed.url)
+    );
+    let processor = feed.feed_processor.processor;
+    let fallbacks = &feed.feed_processor.fallbacks;
+    if processor != ProcessorType::Default || !fallbacks.is_empty() {
+        outline.push_str(&format!(" rsspub:processor=\"{}\"", processor_name(processor)));
+        if let Some(config) = feed.feed_processor.custom_config.as_deref().filter(|c| !c.is_empty()) {
+            outline.push_str(&format!(" rsspub:customConfig=\"{}\"", escape_attr(config)));
+        }
+        if !fallbacks.is_empty() {
+            let names: Vec<String> = fallbacks.iter().map(|p| processor_name(*p)).collect();
+            outline.push_str(&format!(" rsspub:fallbacks=\"{}\"", names.join(",")));
+        }
+    }
+    outline.push_str("/>");
+    outline
+}
+
+/// Escapes an attribute value, keeping line breaks so multi-line YAML survives a round trip.
+fn escape_attr(value: &str) -> String {
+    escape_xml(value)
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+        .replace('\t', "&#9;")
+}
+
+fn processor_name(processor: ProcessorType) -> String {
+    serde_json::to_value(processor)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn parse_processor(name: &str) -> Option<ProcessorType> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+}
+
+/// Reads every feed outline; the enclosing folder outlines, outermost first, become the feed's category path.
+pub fn parse_opml(xml: &str) -> anyhow::Result<Vec<OpmlFeed>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut feeds = Vec::new();
+    // One entry per open <outline>; folders carry their title, feeds carry None.
+    let mut open: Vec<Option<String>> = Vec::new();
+    let mut seen_opml = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"opml" => seen_opml = true,
+            Event::Start(e) if e.name().as_ref() == b"outline" => {
+                let attrs = outline_attributes(&e, &reader)?;
+                match feed_from_attributes(&attrs, &open) {
+                    Some(feed) => {
+                        feeds.push(feed);
+                        open.push(None);
+                    }
+                    None => open.push(attrs.get("title").or(attrs.get("text")).cloned()),
+                }
+            }
+            Event::Empty(e) if e.name().as_ref() == b"outline" => {
+                let attrs = outline_attributes(&e, &reader)?;
+                if let Some(feed) = feed_from_attributes(&attrs, &open) {
+                    feeds.push(feed);
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"outline" => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !seen_opml {
+        anyhow::bail!("Not an OPML document");
+    }
+    Ok(feeds)
+}
+
+/// Plain attributes keep their name. Attributes in `RSSPUB_NAMESPACE` are keyed `rsspub:<name>`
+/// whatever prefix the file binds it to; those in other namespaces are dropped.
+fn outline_attributes(e: &BytesStart, reader: &NsReader<&[u8]>) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut attrs = BTreeMap::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        let key = match reader.resolve_attribute(attr.key) {
+            (ResolveResult::Unbound, name) => String::from_utf8_lossy(name.as_ref()).to_string(),
+            (ResolveResult::Bound(Namespace(ns)), name) if ns == RSSPUB_NAMESPACE.as_bytes() => {
+                format!("rsspub:{}", String::from_utf8_lossy(name.as_ref()))
+            }
+            _ => continue,
+        };
+        attrs.insert(key, attr.decode_and_unescape_value(reader.decoder())?.to_string());
+    }
+    Ok(attrs)
+}
+
+fn feed_from_attributes(attrs: &BTreeMap<String, String>, open: &[Option<String>]) -> Option<OpmlFeed> {
+    let url = attrs.get("xmlUrl").filter(|u| !u.trim().is_empty())?;
+    let folders: Vec<&str> = open.iter().flatten().map(String::as_str).collect();
+    Some(OpmlFeed {
+        url: url.trim().to_string(),
+        name: attrs.get("text").or(attrs.get("title")).cloned(),
+        category: normalize_category(Some(&folders.join("/"))),
+        processor: attrs.get("rsspub:processor").and_then(|p| parse_processor(p)),
+        custom_config: attrs.get("rsspub:customConfig").cloned(),
+        fallbacks: attrs
+            .get("rsspub:fallbacks")
+            .map(|names| names.split(',').filter_map(|p| parse_processor(p.trim())).collect())
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContentProcessor;
+
+    fn feed(url: &str, category: Option<&str>, processor: ProcessorType, config: Option<&str>) -> Feed {
+        Feed {
+            id: None,
+            url: url.to_string(),
+            name: Some(format!("Feed & {}", url)),
+            concurrency_limit: 0,
+            feed_processor: ContentProcessor {
+                id: None,
+                processor,
+                custom_config: config.map(str::to_string),
+                fallbacks: Vec::new(),
+            },
+            etag: None,
+            last_modified: None,
+            last_delivered_at: None,
+            fetch_since_hours: None,
+            max_items: None,
+            enabled: true,
+            health: Default::default(),
+            filter: None,
+            auth: None,
+            proxy_url: None,
+            category: category.map(str::to_string),
+            priority: 0,
+            poll_interval_minutes: None,
+        }
+    }
+
+    #[test]
+    fn test_opml_round_trip() {
+        let yaml = "selector:\n  - '.post'\noutput_mode: html";
+        let mut feeds = vec![
+            feed("https://a.example/rss", None, ProcessorType::Default, None),
+            feed("https://b.example/rss?x=1&y=2", Some("Tech"), ProcessorType::Custom, Some(yaml)),
+            feed("https://c.example/atom", Some("Tech"), ProcessorType::FeedContent, None),
+        ];
+        feeds[1].feed_processor.fallbacks = vec![ProcessorType::DomSmoothie, ProcessorType::FeedContent];
+
+        let parsed = parse_opml(&export_opml(&feeds)).unwrap();
+
+        assert_eq!(parsed.len(), 3);
+        let b = parsed.iter().find(|f| f.url == "https://b.example/rss?x=1&y=2").unwrap();
+        assert_eq!(b.name.as_deref(), Some("Feed & https://b.example/rss?x=1&y=2"));
+        assert_eq!(b.category.as_deref(), Some("Tech"));
+        assert_eq!(b.processor, Some(ProcessorType::Custom));
+        assert_eq!(b.custom_config.as_deref(), Some(yaml));
+        assert_eq!(b.fallbacks, vec![ProcessorType::DomSmoothie, ProcessorType::FeedContent]);
+        let a = parsed.iter().find(|f| f.url == "https://a.example/rss").unwrap();
+        assert_eq!(a.category, None);
+        assert_eq!(a.processor, None);
+    }
+
+    #[test]
+    fn test_nested_folders_become_category_paths() {
+        let feeds = vec![
+            feed("https://a.example/rss", Some("Tech/AI"), ProcessorType::Default, None),
+            feed("https://b.example/rss", Some("Tech"), ProcessorType::Default, None),
+            feed("https://c.example/rss", Some("News/World/Europe"), ProcessorType::Default, None),
+            feed("https://d.example/rss", Some("Tech B"), ProcessorType::Default, None),
+        ];
+
+        let xml = export_opml(&feeds);
+        assert_eq!(xml.matches("title=\"Tech\">").count(), 1);
+
+        let parsed = parse_opml(&xml).unwrap();
+        let category = |url: &str| parsed.iter().find(|f| f.url == url).unwrap().category.clone();
+        assert_eq!(category("https://a.example/rss").as_deref(), Some("Tech/AI"));
+        assert_eq!(category("https://b.example/rss").as_deref(), Some("Tech"));
+        assert_eq!(category("https://c.example/rss").as_deref(), Some("News/World/Europe"));
+        assert_eq!(category("https://d.example/rss").as_deref(), Some("Tech B"));
+
+        assert!(category_matches(Some("Tech/AI"), "Tech"));
+        assert!(!category_matches(Some("Tech B"), "Tech"));
+        assert_eq!(normalize_category(Some(" Tech / AI /")).as_deref(), Some("Tech/AI"));
+    }
+
+    #[test]
+    fn test_settings_are_matched_by_namespace() {
+        let xml = format!(
+            r#"<opml version="2.0" xmlns:rp="{}" xmlns:other="https://other.example/ns"><body>
+                <outline xmlUrl="https://a.example/rss" rp:processor="text_only"/>
+                <outline xmlUrl="https://b.example/rss" other:processor="text_only"/>
+            </body></opml>"#,
+            RSSPUB_NAMESPACE
+        );
+
+        let parsed = parse_opml(&xml).unwrap();
+        assert_eq!(parsed[0].processor, Some(ProcessorType::TextOnly));
+        assert_eq!(parsed[1].processor, None);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::models::{CustomExtractorConfig, ContentProcessor, OutputMode, ProcessorType};
+use crate::models::{CustomExtractorConfig, ContentProcessor, FeedAuth, OutputMode, ProcessorType};
+use crate::util::page_cache;
 use arc_swap::ArcSwap;
 use dom_query::Document;
 use dom_smoothie::{CandidateSelectMode, Config, TextMode};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 
@@ -25,14 +26,58 @@ pub fn get_domain_override(url: &str) -> Option<Arc<ContentProcessor>> {
     DOMAIN_OVERRIDES.get()?.load().get(&domain).cloned()
 }
 
+/// Pages fetched for one multi-page article unless the extractor config says otherwise.
+pub const DEFAULT_MAX_PAGES: usize = 5;
+
+/// An article pulled out of a page, with whatever metadata the page exposes.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractedContent {
+    pub title: String,
+    pub content: String,
+    pub byline: Option<String>,
+    pub excerpt: Option<String>,
+    /// Absolute URL of the page's lead image, left unset by extractors that drop images.
+    pub lead_image: Option<String>,
+}
+
+impl ExtractedContent {
+    fn from_readability(article: dom_smoothie::Article, content: String, url: &str) -> Self {
+        Self {
+            title: article.title,
+            content,
+            byline: non_empty(article.byline),
+            excerpt: non_empty(article.excerpt),
+            lead_image: non_empty(article.image).and_then(|src| resolve_url(url, &src)),
+        }
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn resolve_url(base: &str, href: &str) -> Option<String> {
+    let resolved = url::Url::parse(base).ok()?.join(href).ok()?;
+    matches!(resolved.scheme(), "http" | "https").then(|| resolved.to_string())
+}
+
 pub trait ContentExtractor: Send + Sync {
-    fn extract(&self, html: &str, url: &str) -> anyhow::Result<(String, String)>;
+    fn extract(&self, html: &str, url: &str) -> anyhow::Result<ExtractedContent>;
+
+    /// Selector for the next-page link; `None` falls back to `rel="next"`.
+    fn next_page_selector(&self) -> Option<&str> {
+        None
+    }
+
+    fn max_pages(&self) -> usize {
+        DEFAULT_MAX_PAGES
+    }
 }
 
 pub struct DefaultExtractor;
 
 impl ContentExtractor for DefaultExtractor {
-    fn extract(&self, html: &str, url: &str) -> anyhow::Result<(String, String)> {
+    fn extract(&self, html: &str, url: &str) -> anyhow::Result<ExtractedContent> {
         let cfg = Config {
             text_mode: TextMode::Markdown,
             ..Default::default()
@@ -41,14 +86,15 @@ impl ContentExtractor for DefaultExtractor {
         let extracted = readability
             .parse()
             .map_err(|e| anyhow::anyhow!("DomSmoothie error: {:?}", e))?;
-        Ok((extracted.title, extracted.content.to_string()))
+        let content = extracted.content.to_string();
+        Ok(ExtractedContent::from_readability(extracted, content, url))
     }
 }
 
 pub struct DomSmoothieExtractor;
 
 impl ContentExtractor for DomSmoothieExtractor {
-    fn extract(&self, html: &str, url: &str) -> anyhow::Result<(String, String)> {
+    fn extract(&self, html: &str, url: &str) -> anyhow::Result<ExtractedContent> {
         let cfg = Config {
             text_mode: TextMode::Markdown,
             candidate_select_mode: CandidateSelectMode::DomSmoothie,
@@ -58,14 +104,15 @@ impl ContentExtractor for DomSmoothieExtractor {
         let extracted = readability
             .parse()
             .map_err(|e| anyhow::anyhow!("DomSmoothie error: {:?}", e))?;
-        Ok((extracted.title, extracted.content.to_string()))
+        let content = extracted.content.to_string();
+        Ok(ExtractedContent::from_readability(extracted, content, url))
     }
 }
 
 pub struct TextOnlyExtractor;
 
 impl ContentExtractor for TextOnlyExtractor {
-    fn extract(&self, html: &str, url: &str) -> anyhow::Result<(String, String)> {
+    fn extract(&self, html: &str, url: &str) -> anyhow::Result<ExtractedContent> {
         let cfg = Config {
             text_mode: TextMode::Markdown,
             candidate_select_mode: CandidateSelectMode::DomSmoothie,
@@ -82,7 +129,10 @@ impl ContentExtractor for TextOnlyExtractor {
             images.remove();
         }
         
-        Ok((extracted.title, doc.html().to_string()))
+        Ok(ExtractedContent {
+            lead_image: None,
+            ..ExtractedContent::from_readability(extracted, doc.html().to_string(), url)
+        })
     }
 }
 
@@ -96,10 +146,22 @@ impl CustomExtractor {
             .map_err(|e| anyhow::anyhow!("Invalid YAML config: {}", e))?;
         Ok(Self { config })
     }
+
+    /// Checks the config can extract anything at all: at least one selector, and every selector parses.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.config.selector.iter().all(|s| s.trim().is_empty()) {
+            anyhow::bail!("Custom config needs at least one selector");
+        }
+        let selectors = self.config.selector.iter().chain(&self.config.discard).chain(&self.config.next_page);
+        for selector in selectors.filter(|s| !s.trim().is_empty()) {
+            dom_query::Matcher::new(selector).map_err(|_| anyhow::anyhow!("Invalid selector '{}'", selector))?;
+        }
+        Ok(())
+    }
 }
 
 impl ContentExtractor for CustomExtractor {
-    fn extract(&self, html: &str, _url: &str) -> anyhow::Result<(String, String)> {
+    fn extract(&self, html: &str, url: &str) -> anyhow::Result<ExtractedContent> {
         let use_text_mode = self.config.output_mode == OutputMode::Text;
 
         let document = Document::from(html);
@@ -134,7 +196,32 @@ impl ContentExtractor for CustomExtractor {
         }
         let content=content.to_string();
 
-        Ok((title, content))
+        let meta = |selector: &str| {
+            document
+                .try_select(selector)
+                .and_then(|m| m.attr("content"))
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+        };
+        let lead_image = meta("meta[property='og:image']")
+            .and_then(|src| resolve_url(url, &src))
+            .filter(|_| !use_text_mode);
+
+        Ok(ExtractedContent {
+            title,
+            content,
+            byline: meta("meta[name='author']").or_else(|| meta("meta[property='article:author']")),
+            excerpt: meta("meta[name='description']").or_else(|| meta("meta[property='og:description']")),
+            lead_image,
+        })
+    }
+
+    fn next_page_selector(&self) -> Option<&str> {
+        self.config.next_page.as_deref().filter(|s| !s.trim().is_empty())
+    }
+
+    fn max_pages(&self) -> usize {
+        self.config.max_pages.unwrap_or(DEFAULT_MAX_PAGES).max(1)
     }
 }
 pub fn create_extractor(processor: Option<&ContentProcessor>) -> anyhow::Result<Box<dyn ContentExtractor>> {
@@ -149,31 +236,547 @@ pub fn create_extractor(processor: Option<&ContentProcessor>) -> anyhow::Result<
                 .ok_or_else(|| anyhow::anyhow!("Custom processor requires custom_config"))?;
             Ok(Box::new(CustomExtractor::new(custom_config)?))
         }
-        ProcessorType::Default => Ok(Box::new(DefaultExtractor)),
+        // Only reached when the page has to be scraped anyway, e.g. Read It Later or an empty feed body.
+        ProcessorType::FeedContent | ProcessorType::Default => Ok(Box::new(DefaultExtractor)),
     }
 }
 
+/// Rejects a processor chain that would only fail once a digest is generated, e.g. a Custom config that
+/// does not parse.
+pub fn validate_processor(
+    processor: ProcessorType,
+    fallbacks: &[ProcessorType],
+    custom_config: Option<&str>,
+) -> anyhow::Result<()> {
+    if processor != ProcessorType::Custom && !fallbacks.contains(&ProcessorType::Custom) {
+        return Ok(());
+    }
+    let config = custom_config
+        .filter(|c| !c.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Custom processor requires custom_config"))?;
+    CustomExtractor::new(config)?.validate()
+}
+
+/// Hints for tuning a processor against a page: Custom selectors that match nothing, and empty output.
+pub fn extraction_warnings(html: &str, processor: &ContentProcessor, title: &str, content: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if processor.chain().contains(&ProcessorType::Custom)
+        && let Some(extractor) = processor.custom_config.as_deref().and_then(|c| CustomExtractor::new(c).ok())
+    {
+        let document = Document::from(html);
+        let matches_nothing = |selector: &str| document.try_select(selector).is_none();
+        let config = &extractor.config;
+        for selector in config.selector.iter().filter(|s| matches_nothing(s)) {
+            warnings.push(format!("Selector '{}' matched nothing", selector));
+        }
+        for selector in config.discard.iter().filter(|s| matches_nothing(s)) {
+            warnings.push(format!("Discard selector '{}' matched nothing", selector));
+        }
+        if let Some(selector) = extractor.next_page_selector().filter(|s| matches_nothing(s)) {
+            warnings.push(format!("Next page selector '{}' matched nothing", selector));
+        }
+    }
+    if title.trim().is_empty() || title == "Untitled" {
+        warnings.push("No title found".to_string());
+    }
+    if Document::from(content).select("body").text().trim().is_empty() {
+        warnings.push("Extracted content is empty".to_string());
+    }
+    warnings
+}
+
 pub fn extract_domain(url: &str) -> Option<String> {
     url::Url::parse(url)
         .ok()
         .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
 }
 
-pub async fn fetch_full_content(client: &Client, url: &str) -> anyhow::Result<(String, String)> {
-    fetch_full_content_with_processor(client, url, None).await
+#[derive(Clone)]
+pub struct FetchedContent {
+    pub title: String,
+    pub content: String,
+    /// The page's `rel=canonical` URL, normalised with `util::canonicalize_url`.
+    pub canonical_url: Option<String>,
+    pub byline: Option<String>,
+    pub excerpt: Option<String>,
+    pub lead_image: Option<String>,
+    /// The step of the processor chain whose output was used.
+    pub step: ProcessorType,
+    /// Retried requests spent downloading the article's pages; 0 when served from the cache.
+    pub retries: u32,
+}
+
+impl FetchedContent {
+    fn from_chain(result: ChainResult, canonical_url: Option<String>) -> Self {
+        let extracted = result.extracted;
+        Self {
+            title: extracted.title,
+            content: extracted.content,
+            canonical_url,
+            byline: extracted.byline,
+            excerpt: extracted.excerpt,
+            lead_image: extracted.lead_image,
+            step: result.step,
+            retries: 0,
+        }
+    }
+}
+
+pub async fn fetch_full_content(client: &Client, url: &str) -> anyhow::Result<FetchedContent> {
+    fetch_full_content_with_processor(client, url, None, None, None).await
 }
+
+/// Fetches and extracts an article with the processor chain that applies to `url`. `feed_content` is
+/// the body embedded in the feed, used by the `FeedContent` step.
 pub async fn fetch_full_content_with_processor(
     client: &Client,
     url: &str,
     processor: Option<&ContentProcessor>,
-) -> anyhow::Result<(String, String)> {
-    let html = client.get(url).send().await?.text().await?;
+    auth: Option<&FeedAuth>,
+    feed_content: Option<&str>,
+) -> anyhow::Result<FetchedContent> {
+    let domain_override = get_domain_override(url);
+    let processor = domain_override.as_deref().or(processor);
+    let custom_config = processor.and_then(|p| p.custom_config.as_deref());
+    let feed_content = feed_content.filter(|c| !c.trim().is_empty());
+    let mut chain = processor.map(ContentProcessor::chain).unwrap_or_else(|| vec![ProcessorType::Default]);
+    // A feed-content-only processor still has to scrape items that carry no body.
+    if feed_content.is_none() && !chain.iter().any(|s| s.scrapes_page()) {
+        chain.push(ProcessorType::Default);
+    }
+
+    // Feed content that is good enough saves downloading the page at all.
+    let feed_first = chain[0] == ProcessorType::FeedContent
+        && feed_content.is_some_and(|c| quality_issue(c).is_none());
+    if feed_first || !chain.iter().any(|s| s.scrapes_page()) {
+        let result = run_chain(None, url, &chain, custom_config, feed_content)?;
+        return Ok(FetchedContent::from_chain(result, None));
+    }
 
-    let extractor = if let Some(content_processor) = get_domain_override(url) {
-        create_extractor(Some(&*content_processor))?
-    } else {
-        create_extractor(processor)?
+    let cache_key = page_cache::processor_key(processor);
+    let shared = !uses_credentials(url, auth);
+    if shared && let Some(cached) = page_cache::get_extracted(url, &cache_key).await {
+        return Ok(cached);
+    }
+
+    // Error pages are still extracted, since some sites serve the article with an error status,
+    // but they are never cached.
+    let (html, cacheable, mut retries) = match fetch_html(client, url, auth, false).await {
+        Ok(page) => (Some(page.html), page.status.is_success(), page.retries),
+        Err(e) if feed_content.is_some() && chain.contains(&ProcessorType::FeedContent) => {
+            tracing::warn!("Using feed content for {}: {}", url, e);
+            (None, false, 0)
+        }
+        Err(e) => return Err(e),
     };
-    
-    extractor.extract(&html, url)
-}
\ No newline at end of file
+    let mut result = run_chain(html.as_deref(), url, &chain, custom_config, feed_content)?;
+    for rejected in &result.rejected {
+        tracing::warn!("Skipped {} for {}", rejected, url);
+    }
+    tracing::info!("Extracted {} with {:?}", url, result.step);
+
+    let canonical_url = html.as_deref().and_then(|html| find_canonical_url(html, url));
+    let content = &mut result.extracted.content;
+    if let (Some(extractor), Some(html)) = (&result.extractor, &html) {
+        let mut visited = vec![url.to_string()];
+        let mut next = find_next_page(html, url, extractor.next_page_selector());
+        while let Some(page_url) = next.take() {
+            if visited.len() >= extractor.max_pages() || visited.contains(&page_url) {
+                break;
+            }
+            let page_html = match fetch_html(client, &page_url, auth, true).await {
+                Ok(page) => {
+                    retries += page.retries;
+                    page.html
+                }
+                Err(e) => {
+                    tracing::warn!("Stopping at page {} of {}: {}", visited.len(), url, e);
+                    break;
+                }
+            };
+            match extractor.extract(&page_html, &page_url) {
+                Ok(page) => {
+                    content.push('\n');
+                    content.push_str(&page.content);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to extract {}: {}", page_url, e);
+                    break;
+                }
+            }
+            next = find_next_page(&page_html, &page_url, extractor.next_page_selector());
+            visited.push(page_url);
+        }
+    }
+
+    let mut fetched = FetchedContent::from_chain(result, canonical_url);
+    fetched.retries = retries;
+    if shared && cacheable && fetched.step.scrapes_page() {
+        page_cache::put_extracted(url, &cache_key, &fetched).await;
+    }
+    Ok(fetched)
+}
+
+/// Articles with fewer words than this are treated as a failed extraction.
+pub const MIN_ARTICLE_WORDS: usize = 50;
+/// Short pages mentioning one of these phrases are a paywall or consent wall rather than the article.
+const WALL_MAX_WORDS: usize = 400;
+const WALL_PHRASES: &[&str] = &[
+    "subscribe to continue",
+    "subscribe to read",
+    "to continue reading",
+    "already a subscriber",
+    "available to subscribers",
+    "sign in to read",
+    "log in to continue",
+    "create a free account",
+    "we use cookies",
+    "accept all cookies",
+    "manage cookie",
+    "please enable javascript",
+    "disable your ad blocker",
+];
+
+/// Why extracted content should not be used: too short to be the article, or a paywall or cookie banner.
+pub fn quality_issue(content: &str) -> Option<String> {
+    let text = Document::from(content).select("body").text().to_lowercase();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() < MIN_ARTICLE_WORDS {
+        return Some(format!("only {} words", words.len()));
+    }
+    let text = words.join(" ");
+    if words.len() < WALL_MAX_WORDS
+        && let Some(phrase) = WALL_PHRASES.iter().find(|p| text.contains(*p))
+    {
+        return Some(format!("looks like a paywall or consent banner (\"{}\")", phrase));
+    }
+    None
+}
+
+fn word_count(content: &str) -> usize {
+    Document::from(content).select("body").text().split_whitespace().count()
+}
+
+/// The outcome of a processor chain.
+pub struct ChainResult {
+    pub extracted: ExtractedContent,
+    pub step: ProcessorType,
+    /// Extractor of the winning step, used for following pages; `None` for feed content.
+    pub extractor: Option<Box<dyn ContentExtractor>>,
+    /// Steps that were skipped, with the reason.
+    pub rejected: Vec<String>,
+}
+
+/// Runs each step over the page and keeps the first output that passes `quality_issue`, or the longest
+/// one when none does. `html` is `None` when the page was not fetched, leaving only the feed content step.
+pub fn run_chain(
+    html: Option<&str>,
+    url: &str,
+    chain: &[ProcessorType],
+    custom_config: Option<&str>,
+    feed_content: Option<&str>,
+) -> anyhow::Result<ChainResult> {
+    let mut rejected = Vec::new();
+    let mut fallback: Option<(usize, ChainResult)> = None;
+    for &step in chain {
+        let attempt = match (step, html) {
+            (ProcessorType::FeedContent, _) => feed_content
+                .filter(|c| !c.trim().is_empty())
+                .map(|c| {
+                    let extracted = ExtractedContent {
+                        content: c.to_string(),
+                        ..Default::default()
+                    };
+                    (extracted, None)
+                })
+                .ok_or_else(|| anyhow::anyhow!("feed has no content")),
+            (_, Some(html)) => {
+                let processor = ContentProcessor {
+                    id: None,
+                    processor: step,
+                    custom_config: custom_config.map(str::to_string),
+                    fallbacks: Vec::new(),
+                };
+                create_extractor(Some(&processor))
+                    .and_then(|extractor| Ok((extractor.extract(html, url)?, Some(extractor))))
+            }
+            (_, None) => Err(anyhow::anyhow!("page not fetched")),
+        };
+        let (extracted, extractor) = match attempt {
+            Ok(attempt) => attempt,
+            Err(e) => {
+                rejected.push(format!("{:?}: {}", step, e));
+                continue;
+            }
+        };
+        let issue = quality_issue(&extracted.content);
+        let result = ChainResult {
+            extracted,
+            step,
+            extractor,
+            rejected: Vec::new(),
+        };
+        match issue {
+            None => {
+                return Ok(ChainResult { rejected, ..result });
+            }
+            Some(reason) => {
+                rejected.push(format!("{:?}: {}", step, reason));
+                let words = word_count(&result.extracted.content);
+                if fallback.as_ref().is_none_or(|(best, _)| words > *best) {
+                    fallback = Some((words, result));
+                }
+            }
+        }
+    }
+    match fallback {
+        Some((_, result)) => Ok(ChainResult { rejected, ..result }),
+        None => Err(anyhow::anyhow!("No processor produced content ({})", rejected.join("; "))),
+    }
+}
+
+pub struct FetchedPage {
+    pub html: String,
+    pub status: StatusCode,
+    /// Retried requests before the response arrived.
+    pub retries: u32,
+}
+
+/// Whether a request for `url` carries feed credentials or domain cookies. Such pages may differ
+/// from what an anonymous request sees, so they are kept out of the shared cache.
+fn uses_credentials(url: &str, auth: Option<&FeedAuth>) -> bool {
+    auth.is_some() || crate::util::cookies::cookie_header(url).is_some()
+}
+
+/// Page HTML and its status, from the cache or the network. Only successful anonymous responses are
+/// cached; error pages are returned as-is unless `require_success` is set.
+pub async fn fetch_html(
+    client: &Client,
+    url: &str,
+    auth: Option<&FeedAuth>,
+    require_success: bool,
+) -> anyhow::Result<FetchedPage> {
+    let shared = !uses_credentials(url, auth);
+    if shared && let Some(html) = page_cache::get_page(url).await {
+        return Ok(FetchedPage { html, status: StatusCode::OK, retries: 0 });
+    }
+    let (response, retries) = fetch_page(client, url, auth).await;
+    let response = response?;
+    let status = response.status();
+    if require_success && !status.is_success() {
+        response.error_for_status_ref()?;
+    }
+    let html = response.text().await?;
+    if shared && status.is_success() {
+        page_cache::put_page(url, &html).await;
+    }
+    Ok(FetchedPage { html, status, retries })
+}
+
+async fn fetch_page(client: &Client, url: &str, auth: Option<&FeedAuth>) -> (reqwest::Result<reqwest::Response>, u32) {
+    let request = client
+        .get(url)
+        .timeout(crate::util::http::timeout_for(crate::util::http::RequestKind::Article));
+    let mut request = crate::util::cookies::with_domain_cookies(request, url);
+    if let Some(auth) = auth {
+        request = crate::util::http::apply_feed_auth(request, auth);
+    }
+    crate::util::http::send_with_retry(request).await
+}
+
+/// Finds the URL of the article's next page on the same host. A configured selector is trusted as
+/// is; `rel="next"` is only followed when it looks like a continuation of the current page, since
+/// some sites use it to point at the next post instead.
+pub fn find_next_page(html: &str, url: &str, selector: Option<&str>) -> Option<String> {
+    let base = url::Url::parse(url).ok()?;
+    let document = Document::from(html);
+    let href = match selector {
+        Some(selector) => document.try_select(selector)?.nodes().iter().find_map(|n| n.attr("href")),
+        None => document
+            .select("link[rel~='next'][href], a[rel~='next'][href]")
+            .nodes()
+            .first()
+            .and_then(|n| n.attr("href")),
+    }?;
+    let next = base.join(href.trim()).ok()?;
+    if next.host_str() != base.host_str() || next.as_str() == base.as_str() {
+        return None;
+    }
+    if selector.is_none() && !continues_page(&base, &next) {
+        return None;
+    }
+    Some(next.to_string())
+}
+
+fn continues_page(current: &url::Url, next: &url::Url) -> bool {
+    if current.path() == next.path() {
+        return next.query().is_some();
+    }
+    let stem = current.path().trim_end_matches('/');
+    let stem = stem
+        .rsplit_once('.')
+        .filter(|(_, ext)| !ext.contains('/'))
+        .map_or(stem, |(stem, _)| stem);
+    if stem.is_empty() {
+        return false;
+    }
+    // The stem must be followed by a page number, e.g. `-2.html`, `/2/`, `/page/2` or `_p2`.
+    let Some(rest) = next.path().strip_prefix(stem).and_then(|r| r.strip_prefix(['-', '/', '_', '.'])) else {
+        return false;
+    };
+    let rest = ["page", "part", "p"].iter().find_map(|label| rest.strip_prefix(label)).unwrap_or(rest);
+    let rest = rest.strip_prefix(['-', '/', '_']).unwrap_or(rest);
+    let number = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    number.len() < rest.len() && matches!(number.chars().next(), None | Some('.') | Some('/'))
+}
+
+pub fn find_canonical_url(html: &str, url: &str) -> Option<String> {
+    let base = url::Url::parse(url).ok()?;
+    let document = Document::from(html);
+    let href = document
+        .select("link[rel~='canonical'][href]")
+        .nodes()
+        .first()
+        .and_then(|n| n.attr("href"))?;
+    let canonical = base.join(href.trim()).ok()?;
+    if canonical.scheme() != "http" && canonical.scheme() != "https" {
+        return None;
+    }
+    Some(crate::util::canonicalize_url(canonical.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_next_page() {
+        let url = "https://news.example/2024/long-story.html";
+        let page = |head: &str| format!("<html><head>{}</head><body><a class='more' href='/p/2'>More</a></body></html>", head);
+
+        let paged = page("<link rel='next' href='/2024/long-story-2.html'>");
+        assert_eq!(
+            find_next_page(&paged, url, None).as_deref(),
+            Some("https://news.example/2024/long-story-2.html")
+        );
+
+        let query = page("<link rel='next' href='?page=2'>");
+        assert_eq!(
+            find_next_page(&query, url, None).as_deref(),
+            Some("https://news.example/2024/long-story.html?page=2")
+        );
+
+        // Next post in a series, not the next page of this one.
+        let next_post = page("<link rel='next' href='/2024/another-story.html'>");
+        assert_eq!(find_next_page(&next_post, url, None), None);
+        let sequel = page("<link rel='next' href='/2024/long-story-sequel.html'>");
+        assert_eq!(find_next_page(&sequel, url, None), None);
+
+        let page_segment = page("<link rel='next' href='/2024/long-story/page/3/'>");
+        assert_eq!(
+            find_next_page(&page_segment, url, None).as_deref(),
+            Some("https://news.example/2024/long-story/page/3/")
+        );
+
+        let offsite = page("<link rel='next' href='https://other.example/2024/long-story-2.html'>");
+        assert_eq!(find_next_page(&offsite, url, None), None);
+
+        assert_eq!(
+            find_next_page(&next_post, url, Some("a.more")).as_deref(),
+            Some("https://news.example/p/2")
+        );
+    }
+
+    #[test]
+    fn test_validate_processor_and_warnings() {
+        assert!(validate_processor(ProcessorType::Custom, &[], None).is_err());
+        assert!(validate_processor(ProcessorType::Custom, &[], Some("selector: '.post'")).is_err());
+        assert!(validate_processor(ProcessorType::Custom, &[], Some("selector:\n  - 'div[['")).is_err());
+        assert!(validate_processor(ProcessorType::Custom, &[], Some("selector:\n  - '.post'")).is_ok());
+        assert!(validate_processor(ProcessorType::TextOnly, &[], None).is_ok());
+        assert!(validate_processor(ProcessorType::TextOnly, &[ProcessorType::Custom], None).is_err());
+
+        let processor = ContentProcessor {
+            id: None,
+            processor: ProcessorType::Custom,
+            custom_config: Some("selector:\n  - '.post'\ndiscard:\n  - '.ads'".to_string()),
+            fallbacks: Vec::new(),
+        };
+        let html = "<html><head><title>Story</title></head><body><div class='post'>Text</div></body></html>";
+        let extracted = create_extractor(Some(&processor)).unwrap().extract(html, "https://example.com/").unwrap();
+        assert_eq!(
+            extraction_warnings(html, &processor, &extracted.title, &extracted.content),
+            vec!["Discard selector '.ads' matched nothing".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_run_chain_skips_poor_content() {
+        let article = "<p>".to_string() + &"Plenty of words in the actual article body. ".repeat(20) + "</p>";
+        let html = format!(
+            "<html><head><title>Story</title></head><body><div class='banner'>We use cookies. Accept all cookies?</div><article>{}</article></body></html>",
+            article
+        );
+        let config = "selector:\n  - '.banner'";
+        assert!(quality_issue("<div>We use cookies.</div>").is_some());
+        assert!(quality_issue(&article).is_none());
+
+        let chain = [ProcessorType::Custom, ProcessorType::FeedContent, ProcessorType::Default];
+        let result = run_chain(Some(&html), "https://example.com/story", &chain, Some(config), Some("<p>Short summary</p>")).unwrap();
+        assert_eq!(result.step, ProcessorType::Default);
+        assert_eq!(result.rejected.len(), 2);
+        assert!(result.extracted.content.contains("actual article body"));
+
+        // Nothing passes: the longest output is used.
+        let result = run_chain(None, "https://example.com/story", &chain, Some(config), Some("<p>Short summary</p>")).unwrap();
+        assert_eq!(result.step, ProcessorType::FeedContent);
+        assert!(run_chain(None, "https://example.com/story", &chain, Some(config), None).is_err());
+    }
+
+    /// Serves `status` with an article-sized body for every request.
+    async fn serve(status: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let body = format!("<html><body><article><p>{}</p></article></body></html>", "Sign in to keep reading this story. ".repeat(30));
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/story", addr)
+    }
+
+    #[tokio::test]
+    async fn test_error_and_private_pages_are_not_cached() {
+        let conn = crate::db::schema_init::init_db(":memory:").unwrap();
+        page_cache::init(Arc::new(std::sync::Mutex::new(conn)));
+        page_cache::set_ttl_hours(24);
+        let client = Client::builder().no_proxy().build().unwrap();
+        let key = page_cache::processor_key(None);
+
+        let locked = serve("401 Unauthorized").await;
+        assert!(fetch_full_content_with_processor(&client, &locked, None, None, None).await.is_ok());
+        assert!(page_cache::get_extracted(&locked, &key).await.is_none());
+        assert!(page_cache::get_page(&locked).await.is_none());
+
+        let open = serve("200 OK").await;
+        assert!(fetch_full_content_with_processor(&client, &open, None, None, None).await.is_ok());
+        assert!(page_cache::get_extracted(&open, &key).await.is_some());
+
+        // What a feed's credentials unlock is never served to other requests.
+        let private = serve("200 OK").await;
+        let auth = FeedAuth { bearer_token: Some("secret".to_string()), ..Default::default() };
+        assert!(fetch_full_content_with_processor(&client, &private, None, Some(&auth), None).await.is_ok());
+        assert!(page_cache::get_extracted(&private, &key).await.is_none());
+        assert!(page_cache::get_page(&private).await.is_none());
+    }
+}
@@ -0,0 +1,142 @@
+use arc_swap::ArcSwap;
+use chrono::Utc;
+use reqwest::RequestBuilder;
+use reqwest::header::COOKIE;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+static DOMAIN_COOKIES: OnceLock<ArcSwap<HashMap<String, Arc<Vec<NetscapeCookie>>>>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetscapeCookie {
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Unix timestamp; 0 marks a session cookie.
+    pub expires: i64,
+    pub name: String,
+    pub value: String,
+}
+
+impl NetscapeCookie {
+    fn matches(&self, host: &str, path: &str, https: bool, now: i64) -> bool {
+        let domain = self.domain.trim_start_matches('.');
+        let domain_ok = host == domain
+            || ((self.include_subdomains || self.domain.starts_with('.'))
+                && host.ends_with(&format!(".{}", domain)));
+        domain_ok
+            && path.starts_with(&self.path)
+            && (!self.secure || https)
+            && (self.expires == 0 || self.expires > now)
+    }
+}
+
+/// Parses a Netscape/Mozilla `cookies.txt` export. Malformed lines are skipped.
+pub fn parse_netscape_cookies(text: &str) -> Vec<NetscapeCookie> {
+    let mut cookies = Vec::new();
+    for line in text.lines() {
+        // curl marks HttpOnly cookies with this prefix instead of commenting them out.
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        cookies.push(NetscapeCookie {
+            domain: fields[0].trim().to_lowercase(),
+            include_subdomains: fields[1].eq_ignore_ascii_case("TRUE"),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            expires: fields[4].trim().parse().unwrap_or(0),
+            name: fields[5].to_string(),
+            value: fields[6].trim_end_matches('\r').to_string(),
+        });
+    }
+    cookies
+}
+
+pub fn refresh_domain_cookies(jars: Vec<(String, String)>) {
+    let map: HashMap<String, Arc<Vec<NetscapeCookie>>> = jars
+        .into_iter()
+        .map(|(domain, text)| (domain.to_lowercase(), Arc::new(parse_netscape_cookies(&text))))
+        .collect();
+
+    match DOMAIN_COOKIES.get() {
+        Some(swap) => swap.store(Arc::new(map)),
+        None => { let _ = DOMAIN_COOKIES.set(ArcSwap::from_pointee(map)); }
+    }
+}
+
+/// Builds the `Cookie` header value for `url` from the uploaded jar of its domain, if any.
+/// When jars exist for both a domain and its subdomain, the most specific one is used.
+pub fn cookie_header(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    let jars = DOMAIN_COOKIES.get()?.load();
+    let (_, jar) = jars
+        .iter()
+        .filter(|(domain, _)| host == **domain || host.ends_with(&format!(".{}", domain)))
+        .max_by_key(|(domain, _)| domain.len())?;
+
+    let now = Utc::now().timestamp();
+    let https = url.scheme() == "https";
+    let pairs: Vec<String> = jar
+        .iter()
+        .filter(|c| c.matches(&host, url.path(), https, now))
+        .map(|c| format!("{}={}", c.name, c.value))
+        .collect();
+    if pairs.is_empty() {
+        None
+    } else {
+        Some(pairs.join("; "))
+    }
+}
+
+pub fn with_domain_cookies(request: RequestBuilder, url: &str) -> RequestBuilder {
+    match cookie_header(url) {
+        Some(cookies) => request.header(COOKIE, cookies),
+        None => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_netscape_cookies() {
+        let text = "# Netscape HTTP Cookie File\n\
+            .example.com\tTRUE\t/\tTRUE\t0\tsession\tabc\n\
+            #HttpOnly_www.example.com\tFALSE\t/account\tFALSE\t4102444800\tauth\txyz\r\n\
+            broken line\n";
+
+        let cookies = parse_netscape_cookies(text);
+
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].name, "session");
+        assert!(cookies[0].include_subdomains && cookies[0].secure);
+        assert_eq!(cookies[1].domain, "www.example.com");
+        assert_eq!(cookies[1].value, "xyz");
+
+        assert!(cookies[0].matches("news.example.com", "/a", true, 0));
+        assert!(!cookies[0].matches("news.example.com", "/a", false, 0));
+        assert!(cookies[1].matches("www.example.com", "/account/x", false, 0));
+        assert!(!cookies[1].matches("example.com", "/account", false, 0));
+        assert!(!cookies[1].matches("www.example.com", "/account", false, 4102444801));
+    }
+
+    #[test]
+    fn test_cookie_header_prefers_most_specific_jar() {
+        refresh_domain_cookies(vec![
+            ("example.com".to_string(), ".example.com\tTRUE\t/\tFALSE\t0\tsite\t1\n".to_string()),
+            ("news.example.com".to_string(), "news.example.com\tFALSE\t/\tFALSE\t0\tnews\t2\n".to_string()),
+        ]);
+
+        assert_eq!(cookie_header("https://news.example.com/a").as_deref(), Some("news=2"));
+        assert_eq!(cookie_header("https://www.example.com/").as_deref(), Some("site=1"));
+        assert_eq!(cookie_header("https://other.org/"), None);
+    }
+}
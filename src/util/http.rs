@@ -0,0 +1,489 @@
+use crate::db;
+use crate::models::{FeedAuth, GeneralConfig};
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_LANGUAGE, RETRY_AFTER};
+use reqwest::{redirect, Client, RequestBuilder, Response, StatusCode};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{error, warn};
+
+static PROXY_SETTINGS: OnceLock<ArcSwap<ProxySettings>> = OnceLock::new();
+static RETRY_POLICY: OnceLock<ArcSwap<RetryPolicy>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff for the given retry (1-based), with jitter in the upper half.
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let millis = delay.as_millis() as u64;
+        Duration::from_millis(fastrand::u64(millis / 2..=millis))
+    }
+}
+
+pub fn refresh_retry_policy(policy: RetryPolicy) {
+    match RETRY_POLICY.get() {
+        Some(swap) => swap.store(std::sync::Arc::new(policy)),
+        None => { let _ = RETRY_POLICY.set(ArcSwap::from_pointee(policy)); }
+    }
+}
+
+fn retry_policy() -> RetryPolicy {
+    RETRY_POLICY.get().map(|p| **p.load()).unwrap_or_default()
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn is_retryable_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || (e.is_request() && !e.is_builder())
+}
+
+/// Reads a `Retry-After` header given either as seconds or as an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Sends `request`, retrying timeouts, connection errors, 429 and 5xx responses with backoff.
+/// Other 4xx responses are returned straight away. Also returns how many retries were made.
+pub async fn send_with_retry(request: RequestBuilder) -> (reqwest::Result<Response>, u32) {
+    let policy = retry_policy();
+    let mut retries = 0;
+    loop {
+        let last_attempt = retries + 1 >= policy.attempts;
+        // Requests with streaming bodies cannot be cloned, so they only get one try.
+        let Some(attempt) = request.try_clone().filter(|_| !last_attempt) else {
+            return (request.send().await, retries);
+        };
+
+        let delay = match attempt.send().await {
+            Ok(resp) if is_retryable_status(resp.status()) => {
+                let retry_after = resp
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| parse_retry_after(v, Utc::now()));
+                match retry_after {
+                    // The server asked for a longer pause than we are willing to wait.
+                    Some(wait) if wait > policy.max_delay => return (Ok(resp), retries),
+                    Some(wait) => wait,
+                    None => policy.backoff(retries + 1),
+                }
+            }
+            Err(e) if is_retryable_error(&e) => {
+                warn!("Request failed, will retry: {}", e);
+                policy.backoff(retries + 1)
+            }
+            result => return (result, retries),
+        };
+
+        retries += 1;
+        warn!("Retrying request (retry {} of {}) in {:?}", retries, policy.attempts - 1, delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[derive(Default)]
+pub struct ProxySettings {
+    pub global: Option<String>,
+    /// Hosts (and their subdomains) that never go through a proxy.
+    pub bypass: Vec<String>,
+    /// Domain override proxies, keyed by domain.
+    pub domains: HashMap<String, String>,
+}
+
+pub fn refresh_proxy_settings(settings: ProxySettings) {
+    match PROXY_SETTINGS.get() {
+        Some(swap) => swap.store(std::sync::Arc::new(settings)),
+        None => { let _ = PROXY_SETTINGS.set(ArcSwap::from_pointee(settings)); }
+    }
+}
+
+/// Re-reads the client settings, retry policy and proxies from the database.
+pub fn reload_http_settings(conn: &Connection) -> rusqlite::Result<()> {
+    let config = db::get_general_config(conn)?;
+    refresh_client_settings(ClientSettings::from(&config));
+    crate::util::page_cache::set_ttl_hours(config.cache_ttl_hours);
+    refresh_retry_policy(RetryPolicy {
+        attempts: config.retry_attempts.max(1) as u32,
+        base_delay: Duration::from_millis(config.retry_base_delay_ms.max(0) as u64),
+        max_delay: Duration::from_millis(config.retry_max_delay_ms.max(0) as u64),
+    });
+    let domains = db::get_domain_overrides(conn)?
+        .into_iter()
+        .filter_map(|o| Some((o.domain, non_empty(o.proxy_url)?)))
+        .collect();
+    let bypass = config
+        .proxy_bypass
+        .unwrap_or_default()
+        .split(',')
+        .map(|h| h.trim().trim_start_matches('.').to_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect();
+    refresh_proxy_settings(ProxySettings {
+        global: non_empty(config.proxy_url),
+        bypass,
+        domains,
+    });
+    Ok(())
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn host_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// How a request reaches its host.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProxyRoute {
+    /// No proxy is configured, so reqwest's usual `HTTP_PROXY`/`HTTPS_PROXY` handling applies.
+    System,
+    /// The host is on the bypass list and is always connected to directly.
+    Direct,
+    Via(String),
+}
+
+/// Picks the proxy for `url`: bypassed hosts go direct, otherwise a matching domain override's
+/// proxy wins over the feed's own proxy, which wins over the global one.
+pub fn resolve_proxy(url: &str, feed_proxy: Option<&str>) -> ProxyRoute {
+    let settings = PROXY_SETTINGS.get().map(|s| s.load_full()).unwrap_or_default();
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .unwrap_or_default();
+
+    if settings.bypass.iter().any(|b| host_matches(&host, b)) {
+        return ProxyRoute::Direct;
+    }
+    settings
+        .domains
+        .iter()
+        .find(|(domain, _)| host_matches(&host, domain))
+        .map(|(_, proxy)| proxy.clone())
+        .or_else(|| feed_proxy.map(str::trim).filter(|p| !p.is_empty()).map(str::to_string))
+        .or_else(|| settings.global.clone())
+        .map_or(ProxyRoute::System, ProxyRoute::Via)
+}
+
+pub fn validate_proxy_url(proxy: Option<&str>) -> Result<(), String> {
+    match proxy.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(proxy) => reqwest::Proxy::all(proxy)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy, e)),
+        None => Ok(()),
+    }
+}
+
+/// The proxy URL without its `user:pass@` part, so credentials are never sent back to the browser.
+pub fn redact_proxy_url(proxy: &str) -> String {
+    let rest = proxy.split_once("://").map_or(proxy, |(_, rest)| rest);
+    let authority = &rest[..rest.find('/').unwrap_or(rest.len())];
+    match authority.rfind('@') {
+        Some(at) => format!("{}{}", &proxy[..proxy.len() - rest.len()], &rest[at + 1..]),
+        None => proxy.to_string(),
+    }
+}
+
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+static CLIENT_SETTINGS: OnceLock<ArcSwap<ClientSettings>> = OnceLock::new();
+/// Route the client takes, and whether it stops at redirects to another host.
+type ClientKey = (ProxyRoute, bool);
+
+static CLIENTS: OnceLock<Mutex<HashMap<ClientKey, Client>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Feed,
+    Article,
+    Image,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    pub user_agent: String,
+    pub accept_language: Option<String>,
+    pub connect_timeout: Duration,
+    /// Longest wait for the next chunk of a response once connected.
+    pub read_timeout: Duration,
+    /// Total time allowed for each kind of request, including the body.
+    pub feed_timeout: Duration,
+    pub article_timeout: Duration,
+    pub image_timeout: Duration,
+    pub max_redirects: usize,
+}
+
+impl From<&GeneralConfig> for ClientSettings {
+    fn from(config: &GeneralConfig) -> Self {
+        let seconds = |s: i32| Duration::from_secs(s.max(1) as u64);
+        ClientSettings {
+            user_agent: non_empty(config.user_agent.clone()).unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            accept_language: non_empty(config.accept_language.clone()),
+            connect_timeout: seconds(config.connect_timeout_seconds),
+            read_timeout: seconds(config.read_timeout_seconds),
+            feed_timeout: seconds(config.feed_timeout_seconds),
+            article_timeout: seconds(config.article_timeout_seconds),
+            image_timeout: seconds(config.image_timeout_seconds),
+            max_redirects: config.max_redirects.max(0) as usize,
+        }
+    }
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings::from(&GeneralConfig::default())
+    }
+}
+
+/// Replaces the client settings and drops the cached clients so they are rebuilt with them.
+pub fn refresh_client_settings(settings: ClientSettings) {
+    match CLIENT_SETTINGS.get() {
+        Some(swap) => swap.store(std::sync::Arc::new(settings)),
+        None => { let _ = CLIENT_SETTINGS.set(ArcSwap::from_pointee(settings)); }
+    }
+    if let Some(clients) = CLIENTS.get() {
+        clients.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+fn client_settings() -> std::sync::Arc<ClientSettings> {
+    CLIENT_SETTINGS.get().map(|s| s.load_full()).unwrap_or_default()
+}
+
+pub fn timeout_for(kind: RequestKind) -> Duration {
+    let settings = client_settings();
+    match kind {
+        RequestKind::Feed => settings.feed_timeout,
+        RequestKind::Article => settings.article_timeout,
+        RequestKind::Image => settings.image_timeout,
+    }
+}
+
+/// Shared client for requests to `url`, routed through the proxy chosen by `resolve_proxy`.
+/// There is one client per proxy for the whole process, so connections are pooled across runs.
+/// Request timeouts are set per request with `timeout_for`.
+pub fn client_for(url: &str, feed_proxy: Option<&str>) -> anyhow::Result<Client> {
+    shared_client(url, feed_proxy, false)
+}
+
+/// Client for requests carrying `auth`. reqwest drops its own credential headers when a redirect
+/// leaves the host but keeps custom ones, so with custom headers redirects stop at a host change.
+pub fn client_with_auth(url: &str, feed_proxy: Option<&str>, auth: Option<&FeedAuth>) -> anyhow::Result<Client> {
+    shared_client(url, feed_proxy, auth.is_some_and(|a| !a.headers.is_empty()))
+}
+
+fn shared_client(url: &str, feed_proxy: Option<&str>, same_host_redirects: bool) -> anyhow::Result<Client> {
+    let key = (resolve_proxy(url, feed_proxy), same_host_redirects);
+    let mut clients = CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_client(&client_settings(), &key.0, same_host_redirects)?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Follows up to `max` redirects, returning the redirect response itself once one points at another host.
+fn same_host_redirect_policy(max: usize) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        let original_host = attempt.previous().first().and_then(|u| u.host_str()).map(str::to_string);
+        if attempt.previous().len() > max {
+            attempt.error("too many redirects")
+        } else if attempt.url().host_str() != original_host.as_deref() {
+            warn!("Not following redirect to another host with custom headers: {}", attempt.url());
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Cookies are not stored on the client: it is shared by every feed, so the only cookies sent
+/// are the configured per-domain jars.
+fn build_client(settings: &ClientSettings, route: &ProxyRoute, same_host_redirects: bool) -> anyhow::Result<Client> {
+    let mut headers = HeaderMap::new();
+    if let Some(language) = &settings.accept_language {
+        match HeaderValue::from_str(language) {
+            Ok(value) => { headers.insert(ACCEPT_LANGUAGE, value); }
+            Err(_) => warn!("Ignoring invalid Accept-Language '{}'", language),
+        }
+    }
+
+    let mut builder = Client::builder()
+        .user_agent(&settings.user_agent)
+        .connect_timeout(settings.connect_timeout)
+        .read_timeout(settings.read_timeout)
+        .redirect(if same_host_redirects {
+            same_host_redirect_policy(settings.max_redirects)
+        } else {
+            redirect::Policy::limited(settings.max_redirects)
+        })
+        .default_headers(headers);
+    match route {
+        ProxyRoute::System => {}
+        ProxyRoute::Direct => builder = builder.no_proxy(),
+        // Connecting directly instead would bypass a proxy the user asked for, so the request fails.
+        ProxyRoute::Via(proxy) => match reqwest::Proxy::all(proxy) {
+            Ok(p) => builder = builder.proxy(p),
+            Err(e) => {
+                error!("Invalid proxy '{}': {}", proxy, e);
+                return Err(anyhow::anyhow!("Invalid proxy '{}': {}", proxy, e));
+            }
+        },
+    }
+    builder.build().map_err(|e| {
+        error!("Failed to build HTTP client: {}", e);
+        anyhow::anyhow!("Failed to build HTTP client: {}", e)
+    })
+}
+
+/// Adds a feed's credentials and custom headers to `request`.
+/// A bearer token takes precedence over a username/password pair.
+pub fn apply_feed_auth(mut request: RequestBuilder, auth: &FeedAuth) -> RequestBuilder {
+    if let Some(token) = auth.bearer_token.as_deref().filter(|t| !t.is_empty()) {
+        request = request.bearer_auth(token);
+    } else if let Some(username) = auth.username.as_deref().filter(|u| !u.is_empty()) {
+        request = request.basic_auth(username, auth.password.as_deref());
+    }
+    for (name, value) in &auth.headers {
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => request = request.header(name, value),
+            _ => warn!("Skipping invalid header '{}'", name),
+        }
+    }
+    request
+}
+
+pub fn validate_feed_auth(auth: &FeedAuth) -> Result<(), String> {
+    for (name, value) in &auth.headers {
+        HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name: {}", name))?;
+        HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header: {}", name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+        let late = policy.backoff(9);
+        assert!(late >= Duration::from_millis(500) && late <= Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_resolve_proxy_and_invalid_proxy() {
+        refresh_proxy_settings(ProxySettings {
+            global: Some("http://global:3128".to_string()),
+            bypass: vec!["internal.lan".to_string()],
+            domains: HashMap::from([("example.com".to_string(), "socks5://tor:9050".to_string())]),
+        });
+
+        assert_eq!(resolve_proxy("http://wiki.internal.lan/", Some("http://feed:8080")), ProxyRoute::Direct);
+        assert_eq!(
+            resolve_proxy("https://news.example.com/", Some("http://feed:8080")),
+            ProxyRoute::Via("socks5://tor:9050".to_string())
+        );
+        assert_eq!(
+            resolve_proxy("https://other.org/", Some("http://feed:8080")),
+            ProxyRoute::Via("http://feed:8080".to_string())
+        );
+        assert_eq!(resolve_proxy("https://other.org/", None), ProxyRoute::Via("http://global:3128".to_string()));
+
+        let invalid = ProxyRoute::Via("http://[bad".to_string());
+        assert!(build_client(&ClientSettings::default(), &invalid, false).is_err());
+    }
+
+    #[test]
+    fn test_redact_proxy_url() {
+        assert_eq!(redact_proxy_url("http://user:p@ss@proxy:3128"), "http://proxy:3128");
+        assert_eq!(redact_proxy_url("socks5://tor:9050"), "socks5://tor:9050");
+        assert_eq!(redact_proxy_url("user:pass@proxy:3128/x@y"), "proxy:3128/x@y");
+    }
+
+    #[tokio::test]
+    async fn test_same_host_redirect_policy_stops_at_other_host() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let response = if request.starts_with("GET /moved ") {
+                    format!("HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/feed\r\nContent-Length: 0\r\n\r\n", port)
+                } else if request.starts_with("GET /away ") {
+                    format!("HTTP/1.1 302 Found\r\nLocation: http://localhost:{}/feed\r\nContent-Length: 0\r\n\r\n", port)
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_string()
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let client = Client::builder().no_proxy().redirect(same_host_redirect_policy(10)).build().unwrap();
+
+        let same_host = client.get(format!("http://127.0.0.1:{}/moved", port)).send().await.unwrap();
+        assert_eq!(same_host.status(), StatusCode::OK);
+        let other_host = client.get(format!("http://127.0.0.1:{}/away", port)).send().await.unwrap();
+        assert_eq!(other_host.status(), StatusCode::FOUND);
+    }
+}
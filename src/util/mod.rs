@@ -2,6 +2,9 @@ use std::sync::LazyLock;
 use regex::Regex;
 use ammonia::Builder;
 pub(crate) mod content_extractors;
+pub(crate) mod cookies;
+pub(crate) mod http;
+pub(crate) mod page_cache;
 
 pub const EPUB_OUTPUT_DIR: &str = "epubs";
 pub const COVER_LOCATION: &str = "static/cover.jpg";
@@ -89,10 +92,61 @@ pub fn wrap_xhtml(title: &str, content: &str) -> String {
     )
 }
 
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_hsenc", "_hsmi",
+    "ref_src",
+];
+
+/// Normalises an article URL so the same page reached through different links compares equal:
+/// lowercases the host, drops the fragment and strips `utm_*` and other tracking parameters.
+pub fn canonicalize_url(input: &str) -> String {
+    let Ok(mut url) = url::Url::parse(input.trim()) else {
+        return input.trim().to_string();
+    };
+    url.set_fragment(None);
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| {
+            let k = k.to_ascii_lowercase();
+            !k.starts_with("utm_") && !TRACKING_PARAMS.contains(&k.as_str())
+        })
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+
+    if let Some(host) = url.host_str().map(|h| h.to_lowercase()) {
+        let _ = url.set_host(Some(&host));
+    }
+    url.to_string()
+}
+
 pub fn escape_xml(s: &str) -> String {
     s.replace("&", "&amp;")
         .replace("<", "&lt;")
         .replace(">", "&gt;")
         .replace("\"", "&quot;")
         .replace("'", "&apos;")
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_url() {
+        assert_eq!(
+            canonicalize_url("https://Example.COM/post?utm_source=rss&id=7&utm_medium=feed#comments"),
+            "https://example.com/post?id=7"
+        );
+        assert_eq!(
+            canonicalize_url("https://example.com/post?fbclid=abc"),
+            "https://example.com/post"
+        );
+        assert_eq!(canonicalize_url("not a url"), "not a url");
+    }
+}
@@ -0,0 +1,99 @@
+use crate::db;
+use crate::models::{ContentProcessor, ProcessorType};
+use crate::util::content_extractors::FetchedContent;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::Connection;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+
+static CACHE_DB: OnceLock<Arc<Mutex<Connection>>> = OnceLock::new();
+static TTL_SECONDS: AtomicI64 = AtomicI64::new(0);
+
+pub fn init(db: Arc<Mutex<Connection>>) {
+    let _ = CACHE_DB.set(db);
+}
+
+/// 0 turns the cache off.
+pub fn set_ttl_hours(hours: i32) {
+    TTL_SECONDS.store(hours.max(0) as i64 * 3600, Ordering::Relaxed);
+}
+
+fn fresh_after() -> Option<DateTime<Utc>> {
+    let ttl = TTL_SECONDS.load(Ordering::Relaxed);
+    (ttl > 0).then(|| Utc::now() - Duration::seconds(ttl))
+}
+
+/// Runs `f` on a blocking thread, so waiting for the shared connection never stalls the runtime.
+async fn with_db<T: Send + 'static>(
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+) -> Option<T> {
+    let db = CACHE_DB.get()?.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = db.lock().map_err(|_| "DB lock failed".to_string())?;
+        f(&conn).map_err(|e| e.to_string())
+    })
+    .await;
+    match result {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            warn!("Page cache error: {}", e);
+            None
+        }
+        Err(e) => {
+            warn!("Page cache task failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Identifies the extraction settings, so changing a custom config never serves stale output.
+pub fn processor_key(processor: Option<&ContentProcessor>) -> String {
+    let config = match processor {
+        Some(p) => format!("{:?}\n{}", p.chain(), p.custom_config.as_deref().unwrap_or_default()),
+        None => format!("{:?}\n", [ProcessorType::Default]),
+    };
+    // FNV-1a, so keys stay the same across builds.
+    let hash = config
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+pub async fn get_page(url: &str) -> Option<String> {
+    let fresh_after = fresh_after()?;
+    let url = crate::util::canonicalize_url(url);
+    with_db(move |conn| db::get_cached_page(conn, &url, fresh_after)).await.flatten()
+}
+
+pub async fn put_page(url: &str, html: &str) {
+    if fresh_after().is_none() {
+        return;
+    }
+    let url = crate::util::canonicalize_url(url);
+    let html = html.to_string();
+    with_db(move |conn| db::save_cached_page(conn, &url, &html, Utc::now())).await;
+}
+
+pub async fn get_extracted(url: &str, processor_key: &str) -> Option<FetchedContent> {
+    let fresh_after = fresh_after()?;
+    let url = crate::util::canonicalize_url(url);
+    let processor_key = processor_key.to_string();
+    with_db(move |conn| db::get_cached_extract(conn, &url, &processor_key, fresh_after)).await.flatten()
+}
+
+pub async fn put_extracted(url: &str, processor_key: &str, fetched: &FetchedContent) {
+    if fresh_after().is_none() {
+        return;
+    }
+    let url = crate::util::canonicalize_url(url);
+    let processor_key = processor_key.to_string();
+    let fetched = fetched.clone();
+    with_db(move |conn| db::save_cached_extract(conn, &url, &processor_key, &fetched, Utc::now())).await;
+}
+
+/// Drops entries past the TTL, or everything when the cache is off.
+pub async fn prune_expired() -> usize {
+    let older_than = fresh_after().unwrap_or_else(Utc::now);
+    with_db(move |conn| db::purge_content_cache(conn, Some(older_than))).await.unwrap_or(0)
+}